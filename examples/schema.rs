@@ -6,6 +6,7 @@ use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 use staking::msg::{
     ExecuteMsg, 
     InstantiateMsg, 
+    MigrateMsg,
     QueryMsg,
     ConfigResponse,
     StakedNftsResponse
@@ -20,6 +21,7 @@ fn main() {
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
     export_schema(&schema_for!(ConfigResponse), &out_dir);
     export_schema(&schema_for!(StakedNftsResponse), &out_dir);
 }
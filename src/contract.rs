@@ -1,39 +1,106 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{to_binary, from_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128, CosmosMsg, WasmMsg, Empty};
+use std::collections::BTreeMap;
 
-use cw2::set_contract_version;
-use cw20::Denom;
+use cosmwasm_std::{to_binary, from_binary, Addr, Binary, Deps, DepsMut, Env, Event, MessageInfo, Response, StdResult, Uint128, CosmosMsg, WasmMsg, Order, Storage};
+
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{Cw20ReceiveMsg, Denom};
 use cw721::{Cw721ReceiveMsg, Cw721ExecuteMsg};
-use cw_utils::must_pay;
+use cw_storage_plus::Bound;
+use cw_utils::{must_pay, nonpayable, one_coin, Expiration};
 
 use crate::util;
+use crate::migrations;
 use crate::error::ContractError;
 use crate::msg::{
     ExecuteMsg, 
     InstantiateMsg, 
+    MigrateMsg,
     QueryMsg, 
     StakedNftResponse, 
     StakedNftsResponse,
     ConfigResponse,
     CollectionResponse,
     NftReceiveMsg, 
+    TokenReceiveMsg,
     EarnInfosResponse,
     AirdropInfosResponse, 
     UserListResponse,
     CollectionListResponse,
     TotalLockedResponse,
+    StreamInfoResponse,
+    TxFeeCreditResponse,
+    PendingOwnerResponse,
+    RolesResponse,
+    RoleMembersResponse,
+    PauseInfoResponse,
+    RarityWeightsResponse,
+    RarityRangesResponse,
+    TokenWeightResponse,
+    MerkleAirdropListResponse,
+    MerkleClaimedResponse,
+    VestingBalance,
+    VestingInfosResponse,
+    UnbondingNftsResponse,
+    UnstakeFeeResponse,
+    RevenueResponse,
+    FundsResponse,
+    AuditResponse,
+    DenomAudit,
+    CollectionAudit,
+    TokenFee,
+    AutoRelockResponse,
+    ClaimOperator,
+    ClaimOperatorsResponse,
+    WithdrawAddressResponse,
 };
 use crate::state::{
     Config, 
     CONFIG,
     COLLECTION_MAP,
+    Collection,
     NftInfo, 
-    UserInfo, AirdropInfo, EarnInfo
+    ReceiveBatch,
+    STREAM_MAP,
+    FEE_CREDIT_MAP,
+    RECEIVE_BATCH_MAP,
+    PENDING_OWNER,
+    PENDING_COLLECTION_OWNER_MAP,
+    Role,
+    ROLE_MAP,
+    AirdropMode,
+    RARITY_DEFAULT_MAP,
+    MerkleAirdrop,
+    MERKLE_AIRDROP_COUNT,
+    MERKLE_AIRDROP_MAP,
+    OPEN_MERKLE_AIRDROP_MAP,
+    MERKLE_CLAIMED_MAP,
+    VESTING_MAP,
+    AUTO_RELOCK_MAP,
+    WITHDRAW_ADDRESS_MAP,
+    UnbondingNft,
+    UNBONDING_MAP,
+    RevenueInfo,
+    REVENUE_MAP,
+    COLLECTION_FUNDS_MAP,
+    ALLOCATED_FUNDS,
+    FundInfo,
+    GLOBAL_PAUSE,
+    COLLECTION_PAUSE_MAP,
+    staked_nft_map,
+    user_map,
 };
 
 const CONTRACT_NAME: &str = "Injstaking by AOI";
-const CONTRACT_VERSION: &str = "1.0";
+const CONTRACT_VERSION: &str = "1.1.0";
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+// Auditing a collection walks all of its users and vestings.
+const AUDIT_DEFAULT_LIMIT: u32 = 1;
+const AUDIT_MAX_LIMIT: u32 = 5;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -49,7 +116,7 @@ pub fn instantiate(
         owner: msg.owner.clone(),
         creator: msg.owner.clone(),
         fee_address: msg.fee_address.clone(),
-        tx_fee: msg.tx_fee.clone(),
+        tx_fee: msg.tx_fee,
         native_token: msg.native_token.clone(),
     };
 
@@ -58,9 +125,32 @@ pub fn instantiate(
     Ok(Response::default())
 }
 
-#[entry_point]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: Empty) -> Result<Response, ContractError> {
-    Ok(Response::default())
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidMigrationContract { 
+            previous_contract: stored.contract 
+        });
+    }
+
+    let previous_version = migrations::parse_version(&stored.version)?;
+    let new_version = migrations::parse_version(CONTRACT_VERSION)?;
+    if previous_version > new_version {
+        return Err(ContractError::InvalidMigrationVersion { 
+            previous_version: stored.version, 
+            new_version: CONTRACT_VERSION.to_string() 
+        });
+    }
+
+    migrations::migrate_from(deps.storage, &previous_version)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("previous_version", stored.version)
+        .add_attribute("new_version", CONTRACT_VERSION)
+    )
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -71,12 +161,84 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::UpdateOwner { 
-            owner 
-        } => util::execute_update_owner(
+        ExecuteMsg::ProposeOwner { 
+            owner,
+            expires,
+        } => util::execute_propose_owner(
+            deps.storage, 
+            env.block,
+            info.sender, 
+            owner,
+            expires,
+        ),
+        ExecuteMsg::AcceptOwner {} => util::execute_accept_owner(
+            deps.storage, 
+            env.block,
+            info.sender, 
+        ),
+        ExecuteMsg::CancelOwner {} => util::execute_cancel_owner(
+            deps.storage, 
+            info.sender, 
+        ),
+        ExecuteMsg::GrantRole { 
+            role,
+            address,
+        } => util::execute_grant_role(
+            deps.storage, 
+            info.sender, 
+            role,
+            address,
+        ),
+        ExecuteMsg::RevokeRole { 
+            role,
+            address,
+        } => util::execute_revoke_role(
+            deps.storage, 
+            info.sender, 
+            role,
+            address,
+        ),
+        ExecuteMsg::UpdatePause { 
+            collection_address,
+            stake,
+            claim,
+            airdrop,
+            withdraw,
+        } => util::execute_update_pause(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            stake,
+            claim,
+            airdrop,
+            withdraw,
+        ),
+        ExecuteMsg::UpdateEmergencyUnstake { 
+            collection_address,
+            enabled,
+        } => util::execute_update_emergency_unstake(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            enabled,
+        ),
+        ExecuteMsg::GrantCollectionManager { 
+            collection_address,
+            address,
+        } => util::execute_grant_collection_manager(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            address,
+        ),
+        ExecuteMsg::RevokeCollectionManager { 
+            collection_address,
+            address,
+        } => util::execute_revoke_collection_manager(
             deps.storage, 
             info.sender, 
-            owner
+            collection_address,
+            address,
         ),
         ExecuteMsg::UpdateFeeAddress { 
             fee_address
@@ -98,6 +260,7 @@ pub fn execute(
             new_unstaking_fee, 
             new_unstaking_fee_percent ,
             cw20_address,
+            airdrop_mode,
         } => util::execute_registe_collection(
             deps.storage, 
             info.sender, 
@@ -105,7 +268,8 @@ pub fn execute(
             owner, 
             cw20_address, 
             new_unstaking_fee, 
-            new_unstaking_fee_percent
+            new_unstaking_fee_percent,
+            airdrop_mode,
         ),
         ExecuteMsg::UpdateCollectionFee { 
             collection_address,
@@ -118,6 +282,35 @@ pub fn execute(
             new_unstaking_fee,
             new_unstaking_fee_percent,
         ),
+        ExecuteMsg::UpdateFeeCurve {
+            collection_address,
+            fee_curve,
+        } => util::execute_update_fee_curve(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            fee_curve,
+        ),
+        ExecuteMsg::UpdateFeeDenom {
+            collection_address,
+            fee_denom,
+            tx_fee,
+        } => util::execute_update_fee_denom(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            fee_denom,
+            tx_fee,
+        ),
+        ExecuteMsg::UpdateTxFeeSplit {
+            collection_address,
+            tx_fee_percent,
+        } => util::execute_update_tx_fee_split(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            tx_fee_percent,
+        ),
         ExecuteMsg::UpdateCollectionState { 
             collection_address,
             is_show,
@@ -127,22 +320,130 @@ pub fn execute(
             collection_address,
             is_show,
         ),
+        ExecuteMsg::UpdateCollectionDenoms { 
+            collection_address,
+            native_denoms,
+        } => util::execute_update_collection_denoms(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            native_denoms,
+        ),
+        ExecuteMsg::UpdateCollectionCw20Denoms { 
+            collection_address,
+            cw20_denoms,
+        } => util::execute_update_collection_cw20_denoms(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            cw20_denoms,
+        ),
+        ExecuteMsg::ProposeCollectionOwner { 
+            collection_address,
+            owner,
+            expires,
+        } => util::execute_propose_collection_owner(
+            deps.storage, 
+            env.block,
+            info.sender, 
+            collection_address,
+            owner,
+            expires,
+        ),
+        ExecuteMsg::AcceptCollectionOwner { 
+            collection_address,
+        } => util::execute_accept_collection_owner(
+            deps.storage, 
+            env.block,
+            info.sender, 
+            collection_address,
+        ),
+        ExecuteMsg::CancelCollectionOwner { 
+            collection_address,
+        } => util::execute_cancel_collection_owner(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+        ),
+        ExecuteMsg::UpdateLockTiers { 
+            collection_address,
+            lock_tiers,
+        } => util::execute_update_lock_tiers(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            lock_tiers,
+        ),
+        ExecuteMsg::UpdateUnbondingDuration { 
+            collection_address,
+            unbonding_duration,
+        } => util::execute_update_unbonding_duration(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            unbonding_duration,
+        ),
+        ExecuteMsg::UpdateCollectionVesting { 
+            collection_address,
+            vesting,
+        } => util::execute_update_collection_vesting(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            vesting,
+        ),
         ExecuteMsg::UpdateCollection {
             collection_address,
             new_cw20_address,
-            new_owner,
             new_duration,
             new_fee_address,
             is_enabled,
+            airdrop_mode,
         } => util::execute_update_collection(
             deps.storage,
             info.sender,
             collection_address,
             new_cw20_address,
-            new_owner,
             new_duration,
             new_fee_address,
             is_enabled,
+            airdrop_mode,
+        ),
+        ExecuteMsg::UpdateRarityWeights { 
+            collection_address,
+            default_weight,
+            weights,
+            ranges,
+        } => util::execute_update_rarity_weights(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            default_weight,
+            weights,
+            ranges,
+        ),
+        ExecuteMsg::RemoveRarityWeights { 
+            collection_address,
+            token_ids,
+            range_starts,
+        } => util::execute_remove_rarity_weights(
+            deps.storage, 
+            info.sender, 
+            collection_address,
+            token_ids,
+            range_starts,
+        ),
+        ExecuteMsg::SyncRarityWeights { 
+            collection_address,
+            start_after,
+            limit,
+        } => execute_sync_rarity_weights(
+            deps, 
+            env, 
+            info, 
+            collection_address,
+            start_after,
+            limit,
         ),
         ExecuteMsg::Charge { 
             collection_address, 
@@ -162,35 +463,37 @@ pub fn execute(
             charge_amount,
         ),
         ExecuteMsg::Withdraw {
+            denom,
             amount,
         } => execute_withdraw(
             deps, 
             env, 
             info, 
+            denom,
             amount,
         ),
         ExecuteMsg::WithdrawAirdrop { 
             collection_address, 
-            cw20_address,
+            denom,
             amount 
         } => execute_withdraw_airdrop(
             deps, 
             env, 
             info, 
             collection_address,
-            cw20_address,
+            denom,
             amount,
         ),
         ExecuteMsg::Airdrop {
             collection_address,
-            cw20_address,
+            denom,
             airdrop_amount,
         } => execute_airdrop(
             deps, 
             env, 
             info,
             collection_address,
-            cw20_address,
+            denom,
             airdrop_amount,
         ),
         ExecuteMsg::AirdropRestart {
@@ -201,14 +504,101 @@ pub fn execute(
             info, 
             collection_address,
         ),
-        /* ExecuteMsg::ReceiveNft (
+        ExecuteMsg::CreateMerkleAirdrop {
+            collection_address,
+            denom,
+            merkle_root,
+            total_amount,
+            expiration,
+        } => execute_create_merkle_airdrop(
+            deps, 
+            env, 
+            info, 
+            collection_address,
+            denom,
+            merkle_root,
+            total_amount,
+            expiration,
+        ),
+        ExecuteMsg::ClaimMerkleAirdrop {
+            airdrop_id,
+            amount,
+            proof,
+        } => execute_claim_merkle_airdrop(
+            deps, 
+            env, 
+            info, 
+            airdrop_id,
+            amount,
+            proof,
+        ),
+        ExecuteMsg::ReclaimMerkleAirdrop {
+            airdrop_id,
+        } => execute_reclaim_merkle_airdrop(
+            deps, 
+            env, 
+            info, 
+            airdrop_id,
+        ),
+        ExecuteMsg::ChargeStream {
+            collection_address,
+            denom,
+            amount,
+        } => execute_charge_stream(
+            deps, 
+            env, 
+            info, 
+            collection_address,
+            denom,
+            amount,
+        ),
+        ExecuteMsg::WithdrawStream {
+            collection_address,
+            amount,
+        } => execute_withdraw_stream(
+            deps, 
+            env, 
+            info, 
+            collection_address,
+            amount,
+        ),
+        ExecuteMsg::UpdateStreamRate {
+            collection_address,
+            reward_per_second,
+        } => execute_update_stream_rate(
+            deps, 
+            env, 
+            info, 
+            collection_address,
+            reward_per_second,
+        ),
+        ExecuteMsg::ReceiveNft (
             msg
         ) => execute_receive_nft(
             deps, 
             env, 
             info, 
             msg
-        ), */
+        ),
+        ExecuteMsg::Receive (
+            msg
+        ) => execute_receive_token(
+            deps, 
+            env, 
+            info, 
+            msg
+        ),
+        ExecuteMsg::DepositTxFee {} => execute_deposit_tx_fee(
+            deps, 
+            info, 
+        ),
+        ExecuteMsg::WithdrawTxFeeCredit {
+            amount,
+        } => execute_withdraw_tx_fee_credit(
+            deps, 
+            info, 
+            amount,
+        ),
         ExecuteMsg::Unstake {
             collection_address,
             token_id
@@ -221,13 +611,49 @@ pub fn execute(
         ),
         ExecuteMsg::Staking {
             collection_address,
-            token_id
+            token_id,
+            tier,
         } => execute_stake(
             deps, 
             env, 
             info, 
             collection_address,
-            token_id
+            token_id,
+            tier,
+        ),
+        ExecuteMsg::SetAutoRelock {
+            collection_address,
+            token_ids,
+            enabled,
+        } => util::execute_set_auto_relock(
+            deps.storage, 
+            env.block.time.seconds(),
+            info.sender, 
+            collection_address,
+            token_ids,
+            enabled,
+        ),
+        ExecuteMsg::WithdrawVested {
+            collection_address,
+            denom,
+            owner,
+            recipient,
+        } => execute_withdraw_vested(
+            deps, 
+            env, 
+            info, 
+            collection_address,
+            denom,
+            owner,
+            recipient,
+        ),
+        ExecuteMsg::WithdrawUnbonded {
+            collection_address,
+        } => execute_withdraw_unbonded(
+            deps, 
+            env, 
+            info, 
+            collection_address,
         ),
         ExecuteMsg::TransferNft {
             collection_address,
@@ -243,48 +669,142 @@ pub fn execute(
         ),
         ExecuteMsg::Claim {
             collection_address,
-            cw20_address,
+            denom,
+            owner,
+            recipient,
         } => execute_claim(
             deps, 
             env, 
             info, 
             collection_address,
-            cw20_address,
+            denom,
+            owner,
+            recipient,
+        ),
+        ExecuteMsg::ApproveClaimOperator {
+            operator,
+            expires,
+        } => util::execute_approve_claim_operator(
+            deps.storage, 
+            env.block,
+            info.sender, 
+            operator,
+            expires,
+        ),
+        ExecuteMsg::RevokeClaimOperator {
+            operator,
+        } => util::execute_revoke_claim_operator(
+            deps.storage, 
+            info.sender, 
+            operator,
+        ),
+        ExecuteMsg::SetWithdrawAddress {
+            address,
+        } => util::execute_set_withdraw_address(
+            deps.storage, 
+            info.sender, 
+            address,
+        ),
+        ExecuteMsg::ClaimAll {
+            collection_addresses,
+            owner,
+            recipient,
+        } => execute_claim_all(
+            deps, 
+            env, 
+            info, 
+            collection_addresses,
+            owner,
+            recipient,
         ),
         ExecuteMsg::Restake {
             collection_address,
-            token_id
+            token_id,
+            tier,
         } => execute_restake(
             deps, 
             env, 
             info, 
             collection_address,
-            token_id
+            token_id,
+            tier,
         ),
     }
 }
 
+/// Re-applies the current weight table to one page of staked NFTs and moves
+/// stream shares to match.
+pub fn execute_sync_rarity_weights(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_address: Addr,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> { 
+    util::check_collection_owner(deps.storage, collection_address.clone(), info.sender)?;
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let nfts = staked_nft_map()
+        .prefix(collection_address.clone())
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<(String, NftInfo)>>>()?;
+    let next_start_after = if nfts.len() == limit { nfts.last().map(|(nft_id, _)| nft_id.clone()) } else { None };
+
+    let mut updated = 0u64;
+    for (nft_id, mut nftinfo) in nfts {
+        let old_share = nftinfo.share();
+        nftinfo.weight = util::token_weight(deps.storage, collection_address.clone(), nft_id.clone())?;
+        if nftinfo.share() == old_share {
+            continue;
+        }
+        util::update_user_shares(
+            deps.storage, 
+            env.block.time.seconds(), 
+            collection_address.clone(), 
+            nftinfo.owner.clone(), 
+            nftinfo.share(), 
+            old_share
+        )?;
+        staked_nft_map().save(deps.storage, (collection_address.clone(), nft_id), &nftinfo)?;
+        updated += 1;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "sync_rarity_weights")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("updated", updated.to_string())
+        .add_attribute("next_start_after", next_start_after.unwrap_or_default())
+    )
+}
+
 pub fn execute_charge (
     deps: DepsMut,
     info: MessageInfo,
     collection_address: Addr,
 ) -> Result<Response, ContractError> { 
-    let cfg = CONFIG.load(deps.storage)?;
     let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
 
-    let receive_amount = match must_pay(&info, &cfg.native_token) {
+    let coin = match one_coin(&info) {
         Ok(it) => it,
         Err(_err) => return Err(ContractError::InsufficientCw20 {  }),
-    }.u128();
+    };
+    if !collection.native_denoms.contains(&coin.denom) {
+        return Err(ContractError::InvalidDenom {  });
+    }
 
-    collection.airdropable += Uint128::from(receive_amount);
+    util::add_airdropable(&mut collection, Denom::Native(coin.denom.clone()), coin.amount);
     COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
+    util::add_funds(deps.storage, Some(collection_address.clone()), Denom::Native(coin.denom.clone()), coin.amount)?;
 
-    return Ok(Response::new()
+    Ok(Response::new()
         .add_attribute("collection_address", collection_address)
         .add_attribute("action", "execute_charge")
-        .add_attribute("charge", receive_amount.to_string())
-    );
+        .add_attribute("denom", coin.denom)
+        .add_attribute("charge", coin.amount)
+    )
 }
 
 pub fn execute_charge_token (
@@ -296,132 +816,157 @@ pub fn execute_charge_token (
 ) -> Result<Response, ContractError> { 
     let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
 
-    let msg;
-    if collection.cw20_address.to_string().len() == 0 {
+    if collection.cw20_address.to_string().is_empty() {
         return Err(crate::ContractError::InvalidCw20Token {  });
-    } else {
-        if util::get_token_amount(deps.querier, Denom::Cw20(collection.cw20_address.clone()), info.sender.clone())? < charge_amount {
-            return Err(crate::ContractError::InsufficientCw20 {  });
-        }
-
-        msg = util::transfer_from_token_message(
-            Denom::Cw20(collection.cw20_address.clone()), 
-            info.sender,
-            env.clone().contract.address.clone(), 
-            charge_amount
-        )?;
+    }
 
-        let index = collection.airdrop_infos.iter().position(|airdrop_info| airdrop_info.cw20_address == collection.cw20_address);
-        match index {
-            Some(index) => {
-                collection.airdrop_infos[index].airdropable += charge_amount;
-            },
-            None => {
-                let airdrop_info = AirdropInfo {
-                    cw20_address: collection.cw20_address.clone(),
-                    total_airdrop: Uint128::zero(),
-                    airdropable: charge_amount,
-                };
-                collection.airdrop_infos.push(airdrop_info.clone())
-            }
-        }
-        COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
+    let denom = Denom::Cw20(collection.cw20_address.clone());
+    if util::get_token_amount(deps.querier, denom.clone(), info.sender.clone())? < charge_amount {
+        return Err(crate::ContractError::InsufficientCw20 {  });
     }
 
+    let msg = util::transfer_from_token_message(
+        denom.clone(), 
+        info.sender,
+        env.contract.address, 
+        charge_amount
+    )?;
+
+    util::add_airdropable(&mut collection, denom.clone(), charge_amount);
+    COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
+    util::add_funds(deps.storage, Some(collection_address.clone()), denom, charge_amount)?;
+
     Ok(Response::new()
         .add_message(msg)
         .add_attribute("collection_address", collection_address)
         .add_attribute("action", "execute_charge")
-        .add_attribute("charge", charge_amount.clone())
+        .add_attribute("charge", charge_amount)
     )
 }
 
+pub fn execute_receive_token(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg
+) -> Result<Response, ContractError> {
+    let msg: TokenReceiveMsg = match from_binary(&wrapper.msg) {
+        Ok(msg) => msg,
+        Err(_err) => return Err(ContractError::InvalidCw20Msg {  }),
+    };
+
+    match msg {
+        TokenReceiveMsg::ChargeCollection {
+            collection_address
+        } => {
+            let mut collection = match COLLECTION_MAP.may_load(deps.storage, collection_address.clone())? {
+                Some(collection) => collection,
+                None => return Err(ContractError::InvalidCollection {  }),
+            };
+
+            // info.sender is the cw20 contract and wrapper.amount is what it moved to us
+            let cw20_address = info.sender;
+            if !collection.cw20_denoms.contains(&cw20_address) {
+                return Err(ContractError::InvalidCw20Token {  });
+            }
+            let charge_amount = wrapper.amount;
+            if charge_amount.is_zero() {
+                return Err(ContractError::InsufficientCw20 {  });
+            }
+
+            util::add_airdropable(&mut collection, Denom::Cw20(cw20_address.clone()), charge_amount);
+            COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
+            util::add_funds(deps.storage, Some(collection_address.clone()), Denom::Cw20(cw20_address.clone()), charge_amount)?;
+
+            Ok(Response::new()
+                .add_attribute("collection_address", collection_address)
+                .add_attribute("action", "execute_charge")
+                .add_attribute("cw20_address", cw20_address)
+                .add_attribute("sender", wrapper.sender)
+                .add_attribute("charge", charge_amount)
+            )
+        },
+        TokenReceiveMsg::Staking {
+            collection_address,
+            token_id,
+            tier,
+        } => {
+            util::check_cw20_fee(deps.storage, collection_address.clone(), info.sender)?;
+            let sender = deps.api.addr_validate(&wrapper.sender)?;
+            stake_nfts(deps, env, sender, collection_address, token_id, tier, wrapper.amount)
+        },
+        TokenReceiveMsg::Restake {
+            collection_address,
+            token_id,
+            tier,
+        } => {
+            util::check_cw20_fee(deps.storage, collection_address.clone(), info.sender)?;
+            let sender = deps.api.addr_validate(&wrapper.sender)?;
+            restake_nfts(deps, env, sender, collection_address, token_id, tier, wrapper.amount)
+        },
+        TokenReceiveMsg::Unstake {
+            collection_address,
+            token_id,
+        } => {
+            util::check_cw20_fee(deps.storage, collection_address.clone(), info.sender)?;
+            let sender = deps.api.addr_validate(&wrapper.sender)?;
+            unstake_nfts(deps, env, sender, collection_address, token_id, wrapper.amount)
+        },
+    }
+}
+
 pub fn execute_withdraw (
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
+    denom: Denom,
     amount: Uint128
 ) -> Result<Response, ContractError> { 
-    util::check_owner(deps.storage, info.sender.clone())?;
-
-    let cfg = CONFIG.load(deps.storage)?;
+    util::check_not_paused(deps.storage, None, |pause| pause.withdraw)?;
+    util::check_role(deps.storage, info.sender.clone(), Role::Treasurer)?;
 
-    if util::get_token_amount(deps.querier, Denom::Native(cfg.native_token.clone()), env.clone().contract.address.clone())? < amount {
-        return Err(crate::ContractError::InsufficientCw20 {  });
+    // Only the part of the balance not owed to collections or fee credit.
+    let balance = util::get_token_amount(deps.querier, denom.clone(), env.contract.address)?;
+    if balance.saturating_sub(util::allocated_amount(deps.storage, &denom)?) < amount {
+        return Err(crate::ContractError::InsufficientFunds {  });
     }
 
-    let msg = util::transfer_token_message(Denom::Native(cfg.native_token.clone()), amount.clone(), info.sender.clone())?;
+    let msg = util::transfer_token_message(denom.clone(), amount, info.sender)?;
 
     Ok(Response::new()
         .add_message(msg)
         .add_attribute("action", "execute_withdraw")
-        .add_attribute("withdraw", amount.clone())
+        .add_attribute("denom", util::denom_string(&denom))
+        .add_attribute("withdraw", amount)
     )
 }
 
 pub fn execute_withdraw_airdrop (
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
     collection_address: Addr,
-    cw20_address: Addr,
+    denom: Denom,
     amount: Uint128
 ) -> Result<Response, ContractError> { 
+    util::check_not_paused(deps.storage, Some(collection_address.clone()), |pause| pause.withdraw)?;
     util::check_collection_owner(deps.storage, collection_address.clone(), info.sender.clone())?;
 
-    let cfg = CONFIG.load(deps.storage)?;
     let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
+    util::sub_airdropable(&mut collection, &denom, amount)?;
+    util::sub_funds(deps.storage, Some(collection_address.clone()), &denom, amount)?;
 
-    if cw20_address.to_string().len() == 0 {
-        if amount > collection.airdropable {
-            return Err(crate::ContractError::InsufficientCw20 {  });
-        }
-
-        if util::get_token_amount(deps.querier, Denom::Native(cfg.native_token.clone()), env.clone().contract.address.clone())? < amount {
-            return Err(crate::ContractError::InsufficientCw20 {  });
-        }
-
-        let msg = util::transfer_token_message(Denom::Native(cfg.native_token.clone()), amount.clone(), info.sender.clone())?;
+    let msg = util::transfer_token_message(denom.clone(), amount, info.sender)?;
 
-        collection.airdropable -= amount;
-        COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
-    
-        Ok(Response::new()
-            .add_message(msg)
-            .add_attribute("collection_address", collection_address)
-            .add_attribute("action", "execute_withdraw")
-            .add_attribute("withdraw", amount.clone())
-        )
-    } else {
-        let index = collection.airdrop_infos.iter().position(|airdrop_info| airdrop_info.cw20_address == cw20_address);
-        match index {
-            Some(index) => {
-                if collection.airdrop_infos[index].airdropable < amount {
-                    return Err(crate::ContractError::InsufficientCw20 {  });
-                }
+    COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
 
-                if util::get_token_amount(deps.querier, Denom::Cw20(cw20_address.clone()), env.clone().contract.address.clone())? < amount {
-                    return Err(crate::ContractError::InsufficientCw20 {  });
-                }
-        
-                let msg = util::transfer_token_message(Denom::Cw20(cw20_address.clone()), amount.clone(), info.sender.clone())?;
-
-                collection.airdrop_infos[index].airdropable -= amount;
-
-                COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
-            
-                Ok(Response::new()
-                    .add_message(msg)
-                    .add_attribute("collection_address", collection_address)
-                    .add_attribute("action", "execute_withdraw")
-                    .add_attribute("withdraw", amount.clone())
-                )
-            },
-            None => {
-                return Err(crate::ContractError::InsufficientCw20 {  });
-            }
-        }
-    }
+    Ok(Response::new()
+        .add_message(msg)
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("action", "execute_withdraw")
+        .add_attribute("denom", util::denom_string(&denom))
+        .add_attribute("withdraw", amount)
+    )
 }
 
 pub fn execute_airdrop(
@@ -429,108 +974,82 @@ pub fn execute_airdrop(
     env: Env,
     info: MessageInfo,
     collection_address: Addr,
-    cw20_address: Addr,
+    denom: Denom,
     airdrop_amount: Uint128,
 ) -> Result<Response, ContractError> { 
+    util::check_not_paused(deps.storage, Some(collection_address.clone()), |pause| pause.airdrop)?;
     util::check_enabled(deps.storage, collection_address.clone())?;
-    util::check_collection_owner(deps.storage, collection_address.clone(), info.sender.clone())?;
+    util::check_collection_manager(deps.storage, collection_address.clone(), info.sender.clone())?;
 
     if airdrop_amount <= Uint128::zero() {
         return Err(crate::ContractError::InvalidAirdrop {  });
     }
     
     let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
-
-    if cw20_address.to_string().len() == 0 {
-        if airdrop_amount > collection.airdropable {
-            return Err(crate::ContractError::InsufficientCw20 {  });
-        }
-    } else {
-        let index = collection.airdrop_infos.iter().position(|airdrop_info| airdrop_info.cw20_address == cw20_address);
-        match index {
-            Some(index) => {
-                if collection.airdrop_infos[index].airdropable < airdrop_amount {
-                    return Err(crate::ContractError::InsufficientCw20 {  });
-                }
-            },
-            None => {
-                return Err(crate::ContractError::InsufficientCw20 {  });
-            }
+    util::sub_airdropable(&mut collection, &denom, airdrop_amount)?;
+
+    let now = env.block.time.seconds();
+    let mut nfts: Vec<(String, NftInfo)> = vec![];
+    for item in staked_nft_map().prefix(collection_address.clone()).range(deps.storage, None, None, Order::Ascending) {
+        let (nft_id, mut nftinfo) = item?;
+        nftinfo.lock_time = util::effective_lock_time(deps.storage, &collection, &nftinfo, now)?;
+        if nftinfo.lock_time > now {
+            nfts.push((nft_id, nftinfo));
         }
     }
 
-    let nft_count = util::get_in_locktime_nft_count(deps.storage, env.block.clone(), collection_address.clone())?;
-
-    if nft_count.is_zero() {
+    if nfts.is_empty() {
         return Err(crate::ContractError::NoUnexpiredNft {  });
     }
 
-    if airdrop_amount < nft_count {
+    let mut weights: BTreeMap<Addr, Uint128> = BTreeMap::new();
+    let mut total_weight = Uint128::zero();
+    for (_, nftinfo) in nfts.iter() {
+        let weight = util::airdrop_weight(&collection, nftinfo, now);
+        *weights.entry(nftinfo.owner.clone()).or_default() += weight;
+        total_weight += weight;
+    }
+
+    if total_weight.is_zero() {
+        return Err(crate::ContractError::NoStakeTime {  });
+    }
+
+    if collection.airdrop_mode == AirdropMode::Even && airdrop_amount < total_weight {
         return Err(crate::ContractError::OverNftCount { 
-            nft_count 
+            nft_count: total_weight
         });
     }
 
-    let airdrop = Uint128::from(airdrop_amount/nft_count);
+    for (nft_id, mut nftinfo) in nfts {
+        nftinfo.airdrop = true;
+        staked_nft_map().save(deps.storage, (collection_address.clone(), nft_id), &nftinfo)?;
+    }
 
-    let users = collection.users.clone();
-    let mut new_users = vec![];
-    for _userinfo in users.iter() {
-        let mut userinfo = _userinfo.clone();
-        let mut nftcount = Uint128::zero();
-        for (index, _nftinfo) in userinfo.staked_nfts.clone().iter().enumerate() {
-            let mut nftinfo = _nftinfo.clone();
-            if nftinfo.lock_time > env.block.time.seconds() && nftinfo.collection_address == collection_address {
-                nftinfo.airdrop = true;
-                nftcount += Uint128::from(1u128);
-            }
-            userinfo.staked_nfts[index] = nftinfo;
+    let mut distributed = Uint128::zero();
+    for (address, weight) in weights {
+        let reward = match collection.airdrop_mode {
+            AirdropMode::Even => airdrop_amount / total_weight * weight,
+            AirdropMode::TimeWeighted => airdrop_amount.multiply_ratio(weight, total_weight),
         };
-
-        if cw20_address.to_string().len() == 0 {
-            userinfo.claimable += airdrop * nftcount;
-        } else {
-            let index = userinfo.earn_infos.iter().position(|earn_info| earn_info.cw20_address == cw20_address);
-            match index {
-                Some(index) => {
-                    userinfo.earn_infos[index].claimable += airdrop * nftcount;
-                },
-                None => {
-                    let earn_info = EarnInfo {
-                        cw20_address: cw20_address.clone(),
-                        total_earned: Uint128::zero(),
-                        claimable: airdrop * nftcount,
-                    };
-                    userinfo.earn_infos.push(earn_info);
-                }
-            }
-        }
-        new_users.push(userinfo);
+        let mut userinfo = user_map().load(deps.storage, (collection_address.clone(), address.clone()))?;
+        util::add_user_reward(&mut userinfo, denom.clone(), reward);
+        user_map().save(deps.storage, (collection_address.clone(), address), &userinfo)?;
+        distributed += reward;
     }
-    collection.users = new_users;
 
-    if cw20_address.to_string().len() == 0 {
-        collection.total_airdrop += airdrop_amount.clone();
-        collection.airdropable -= airdrop_amount.clone();
-    } else {
-        let index = collection.airdrop_infos.iter().position(|airdrop_info| airdrop_info.cw20_address == cw20_address);
-        match index {
-            Some(index) => {
-                collection.airdrop_infos[index].total_airdrop += airdrop_amount.clone();
-                collection.airdrop_infos[index].airdropable -= airdrop_amount.clone();
-            },
-            None => {
-                return Err(crate::ContractError::InsufficientCw20 {  });
-            }
-        }
+    // Rounding dust goes back to the pool instead of being stranded.
+    util::add_airdropable(&mut collection, denom.clone(), airdrop_amount - distributed);
+    if let Some(airdrop_info) = collection.airdrop_infos.iter_mut().find(|airdrop_info| airdrop_info.denom == denom) {
+        airdrop_info.total_airdrop += distributed;
     }
     collection.is_started = false;
     COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
     
     Ok(Response::new()
         .add_attribute("action", "execute_airdrop")
-        .add_attribute("collection_address", collection_address.clone())
-        .add_attribute("airdrop", airdrop_amount.clone())
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("denom", util::denom_string(&denom))
+        .add_attribute("airdrop", distributed)
     )
 }
 
@@ -540,8 +1059,9 @@ pub fn execute_airdrop_restart(
     info: MessageInfo,
     collection_address: Addr
 ) -> Result<Response, ContractError> { 
+    util::check_not_paused(deps.storage, Some(collection_address.clone()), |pause| pause.airdrop)?;
     util::check_enabled(deps.storage, collection_address.clone())?;
-    util::check_collection_owner(deps.storage, collection_address.clone(), info.sender.clone())?;
+    util::check_collection_manager(deps.storage, collection_address.clone(), info.sender.clone())?;
 
     let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
     collection.started_at = env.block.time.seconds();
@@ -549,37 +1069,270 @@ pub fn execute_airdrop_restart(
     COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
 
     Ok(Response::new()
-        .add_attribute("collection_address", collection_address.clone())
+        .add_attribute("collection_address", collection_address)
         .add_attribute("action", "execute_airdrop_restart")
     )
 }
+
+#[allow(clippy::too_many_arguments)]
+pub fn execute_create_merkle_airdrop(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_address: Addr,
+    denom: Denom,
+    merkle_root: String,
+    total_amount: Uint128,
+    expiration: Expiration,
+) -> Result<Response, ContractError> { 
+    util::check_not_paused(deps.storage, Some(collection_address.clone()), |pause| pause.airdrop)?;
+    util::check_collection_owner(deps.storage, collection_address.clone(), info.sender)?;
+    util::check_merkle_root(&merkle_root)?;
+
+    if total_amount.is_zero() {
+        return Err(ContractError::InvalidAirdrop {  });
+    }
+    if expiration.is_expired(&env.block) {
+        return Err(ContractError::MerkleExpired {  });
+    }
+
+    let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
+    util::sub_airdropable(&mut collection, &denom, total_amount)?;
+    COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
+
+    let id = MERKLE_AIRDROP_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    MERKLE_AIRDROP_COUNT.save(deps.storage, &id)?;
+    MERKLE_AIRDROP_MAP.save(deps.storage, id, &MerkleAirdrop {
+        id,
+        collection_address: collection_address.clone(),
+        denom: denom.clone(),
+        merkle_root: merkle_root.clone(),
+        total_amount,
+        claimed_amount: Uint128::zero(),
+        expiration,
+        reclaimed: false,
+    })?;
+    OPEN_MERKLE_AIRDROP_MAP.save(deps.storage, (collection_address.clone(), id), &true)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "create_merkle_airdrop")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("airdrop_id", id.to_string())
+        .add_attribute("denom", util::denom_string(&denom))
+        .add_attribute("merkle_root", merkle_root)
+        .add_attribute("total_amount", total_amount)
+    )
+}
+
+pub fn execute_claim_merkle_airdrop(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    airdrop_id: u64,
+    amount: Uint128,
+    proof: Vec<String>,
+) -> Result<Response, ContractError> { 
+    let mut airdrop = MERKLE_AIRDROP_MAP.load(deps.storage, airdrop_id)?;
+    util::check_not_paused(deps.storage, Some(airdrop.collection_address.clone()), |pause| pause.claim)?;
+
+    if airdrop.expiration.is_expired(&env.block) || airdrop.reclaimed {
+        return Err(ContractError::MerkleExpired {  });
+    }
+    if MERKLE_CLAIMED_MAP.has(deps.storage, (airdrop_id, info.sender.clone())) {
+        return Err(ContractError::MerkleClaimed {  });
+    }
+    util::verify_merkle_proof(&airdrop.merkle_root, &info.sender, amount, proof)?;
+
+    if airdrop.claimed_amount + amount > airdrop.total_amount {
+        return Err(ContractError::InsufficientCw20 {  });
+    }
+    airdrop.claimed_amount += amount;
+    MERKLE_AIRDROP_MAP.save(deps.storage, airdrop_id, &airdrop)?;
+    if airdrop.claimed_amount == airdrop.total_amount {
+        OPEN_MERKLE_AIRDROP_MAP.remove(deps.storage, (airdrop.collection_address.clone(), airdrop_id));
+    }
+    MERKLE_CLAIMED_MAP.save(deps.storage, (airdrop_id, info.sender.clone()), &true)?;
+    util::sub_funds(deps.storage, Some(airdrop.collection_address.clone()), &airdrop.denom, amount)?;
+
+    let msg = util::transfer_token_message(airdrop.denom.clone(), amount, info.sender.clone())?;
+
+    Ok(Response::new()
+        .add_message(msg)
+        .add_attribute("action", "claim_merkle_airdrop")
+        .add_attribute("airdrop_id", airdrop_id.to_string())
+        .add_attribute("address", info.sender)
+        .add_attribute("denom", util::denom_string(&airdrop.denom))
+        .add_attribute("claimed_amount", amount)
+    )
+}
+
+/// Unclaimed funds go back to the collection pool they were taken from.
+pub fn execute_reclaim_merkle_airdrop(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    airdrop_id: u64,
+) -> Result<Response, ContractError> { 
+    let mut airdrop = MERKLE_AIRDROP_MAP.load(deps.storage, airdrop_id)?;
+    util::check_collection_owner(deps.storage, airdrop.collection_address.clone(), info.sender)?;
+
+    if !airdrop.expiration.is_expired(&env.block) {
+        return Err(ContractError::MerkleNotExpired {  });
+    }
+    if airdrop.reclaimed {
+        return Err(ContractError::NoReward {  });
+    }
+
+    let amount = airdrop.total_amount - airdrop.claimed_amount;
+    let mut collection = COLLECTION_MAP.load(deps.storage, airdrop.collection_address.clone())?;
+    util::add_airdropable(&mut collection, airdrop.denom.clone(), amount);
+    COLLECTION_MAP.save(deps.storage, airdrop.collection_address.clone(), &collection)?;
+
+    airdrop.reclaimed = true;
+    MERKLE_AIRDROP_MAP.save(deps.storage, airdrop_id, &airdrop)?;
+    OPEN_MERKLE_AIRDROP_MAP.remove(deps.storage, (airdrop.collection_address.clone(), airdrop_id));
+
+    Ok(Response::new()
+        .add_attribute("action", "reclaim_merkle_airdrop")
+        .add_attribute("collection_address", airdrop.collection_address)
+        .add_attribute("airdrop_id", airdrop_id.to_string())
+        .add_attribute("reclaimed", amount)
+    )
+}
+
+pub fn execute_charge_stream(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_address: Addr,
+    denom: Denom,
+    amount: Uint128,
+) -> Result<Response, ContractError> { 
+    util::check_collection_owner(deps.storage, collection_address.clone(), info.sender)?;
+
+    if amount.is_zero() {
+        return Err(ContractError::InvalidStream {  });
+    }
+
+    let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
+    util::sub_airdropable(&mut collection, &denom, amount)?;
+
+    let mut stream = util::load_stream(deps.storage, collection_address.clone())?;
+    util::accrue_stream(&mut stream, env.block.time.seconds());
+
+    // Accrued rewards are settled lazily in the stream denom, so it can only
+    // change before anything has been streamed.
+    if stream.denom != denom {
+        if !stream.reward_per_share.is_zero() || !stream.remaining.is_zero() {
+            return Err(ContractError::InvalidStream {  });
+        }
+        stream.denom = denom;
+    }
+    stream.remaining += amount;
+
+    COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
+    STREAM_MAP.save(deps.storage, collection_address.clone(), &stream)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "execute_charge_stream")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("charge", amount)
+    )
+}
+
+pub fn execute_withdraw_stream(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_address: Addr,
+    amount: Uint128,
+) -> Result<Response, ContractError> { 
+    util::check_not_paused(deps.storage, Some(collection_address.clone()), |pause| pause.withdraw)?;
+    util::check_collection_owner(deps.storage, collection_address.clone(), info.sender)?;
+
+    let mut stream = util::load_stream(deps.storage, collection_address.clone())?;
+    util::accrue_stream(&mut stream, env.block.time.seconds());
+
+    if amount.is_zero() || amount > stream.remaining {
+        return Err(ContractError::InsufficientCw20 {  });
+    }
+    stream.remaining -= amount;
+
+    let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
+    util::add_airdropable(&mut collection, stream.denom.clone(), amount);
+
+    COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
+    STREAM_MAP.save(deps.storage, collection_address.clone(), &stream)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "execute_withdraw_stream")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("withdraw", amount)
+    )
+}
+
+pub fn execute_update_stream_rate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_address: Addr,
+    reward_per_second: Uint128,
+) -> Result<Response, ContractError> { 
+    util::check_collection_owner(deps.storage, collection_address.clone(), info.sender)?;
+
+    let mut stream = util::load_stream(deps.storage, collection_address.clone())?;
+    util::accrue_stream(&mut stream, env.block.time.seconds());
+    stream.reward_per_second = reward_per_second;
+    STREAM_MAP.save(deps.storage, collection_address.clone(), &stream)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "execute_update_stream_rate")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("reward_per_second", reward_per_second)
+    )
+}
+
 pub fn execute_stake (
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     collection_address: Addr,
-    token_id: Vec<String>
+    token_id: Vec<String>,
+    tier: Option<u32>,
+) -> Result<Response, ContractError> {
+    let fee_paid = util::native_fee_paid(deps.storage, &info, collection_address.clone())?;
+    stake_nfts(deps, env, info.sender, collection_address, token_id, tier, fee_paid)
+}
+
+/// `fee_paid` is in the collection's fee denom, from funds or a cw20 hook.
+fn stake_nfts(
+    deps: DepsMut,
+    env: Env,
+    user_addr: Addr,
+    collection_address: Addr,
+    token_id: Vec<String>,
+    tier: Option<u32>,
+    fee_paid: Uint128,
 ) -> Result<Response, ContractError> {
+    util::check_not_paused(deps.storage, Some(collection_address.clone()), |pause| pause.stake)?;
     util::check_enabled(deps.storage, collection_address.clone())?;
     util::check_airdrop_start(deps.storage, collection_address.clone())?;
 
     let cfg = CONFIG.load(deps.storage)?;
-    let fee_amount = match must_pay(&info, &cfg.native_token) {
-        Ok(it) => it,
-        Err(_err) => return Err(ContractError::InsufficientCw20 {  }),
-    }.u128();
-
-    if fee_amount < u128::from(cfg.tx_fee) {
+    let collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
+    let tx_fee = util::tx_fee(&cfg, &collection);
+    if fee_paid < tx_fee {
         return Err(ContractError::InsufficientCw20 {  })
     }
 
     let mut msgs:Vec<CosmosMsg> = vec![];
-    let mut nftinfos = vec![];
-    let user_addr = info.sender.clone();
+    let mut response = Response::new();
 
-    let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
-   
-    let duration = collection.duration;
+    if let Some((refund_msg, refund)) = util::refund_excess_fee(util::fee_denom(&cfg, &collection), fee_paid, tx_fee, user_addr.clone())? {
+        msgs.push(refund_msg);
+        response = response.add_attribute("refund", refund);
+    }
+    msgs.extend(util::route_fee(deps.storage, &cfg, &collection, util::fee_denom(&cfg, &collection), tx_fee, 100 - collection.tx_fee_percent)?);
 
     for nft_id in token_id.iter() {
         msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
@@ -591,115 +1344,207 @@ pub fn execute_stake (
             funds: vec![],
         }));
         
-        let _nftinfo = NftInfo {
+    }
+    save_staked_nfts(deps.storage, env.block.time.seconds(), &collection, user_addr, token_id, tier)?;
+
+    Ok(response
+        .add_messages(msgs)
+        .add_attribute("action", "nft staking")
+    )
+    
+}
+
+fn save_staked_nfts(
+    storage: &mut dyn Storage,
+    now: u64,
+    collection: &Collection,
+    user_addr: Addr,
+    token_id: Vec<String>,
+    tier: Option<u32>,
+) -> Result<(), ContractError> {
+    let lock_tier = util::get_lock_tier(collection, tier)?;
+    let lock_time = now + util::lock_duration(collection, lock_tier.as_ref());
+    let collection_address = collection.address.clone();
+    let unbonding = UNBONDING_MAP.may_load(storage, (collection_address.clone(), user_addr.clone()))?.unwrap_or_default();
+    let mut shares = Uint128::zero();
+    for nft_id in token_id {
+        if staked_nft_map().has(storage, (collection_address.clone(), nft_id.clone()))
+            || unbonding.iter().any(|nft| nft.nft_id == nft_id) {
+            return Err(ContractError::AlreadyStaked {  });
+        }
+        let nftinfo = NftInfo {
             nft_id: nft_id.clone(),
-            lock_time: duration + env.block.time.seconds(),
+            owner: user_addr.clone(),
+            lock_time,
             airdrop: false,
-            collection_address: collection.address.clone()
+            collection_address: collection_address.clone(),
+            staked_at: now,
+            weight: util::token_weight(storage, collection_address.clone(), nft_id.clone())?,
+            auto_relock: None,
+            tier,
+            multiplier: lock_tier.as_ref().map_or(0, |lock_tier| lock_tier.multiplier),
+            lock_tier: lock_tier.clone(),
         };
-        nftinfos.push(_nftinfo);
+        shares += nftinfo.share();
+        staked_nft_map().save(storage, (collection_address.clone(), nft_id), &nftinfo)?;
     }
 
-    let _userinfo = UserInfo {
-        address: user_addr.clone(),
-        staked_nfts: nftinfos.clone(),
-        total_earnd: Uint128::zero(),
-        claimable: Uint128::zero(),
-        earn_infos: vec![],
+    util::update_user_shares(storage, now, collection_address, user_addr, shares, Uint128::zero())?;
+    Ok(())
+}
+
+pub fn execute_deposit_tx_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let amount = match must_pay(&info, &cfg.native_token) {
+        Ok(it) => it,
+        Err(_err) => return Err(ContractError::InsufficientCw20 {  }),
     };
-    let mut new_users = collection.users.clone();
-    let mut find = false;
-    for (index, user_info) in new_users.iter().enumerate() {
-        if user_info.address == user_addr.clone() {
-            let mut _userinfo2 = user_info.clone();
-            for nftinfo in nftinfos {
-                _userinfo2.staked_nfts.push(nftinfo);
-            }
-            new_users[index] = _userinfo2;
-            find = true;
-            break;
-        }
-    }
-    if !find {
-        new_users.push(_userinfo);
+
+    let credit = FEE_CREDIT_MAP.may_load(deps.storage, info.sender.clone())?.unwrap_or_default() + amount;
+    FEE_CREDIT_MAP.save(deps.storage, info.sender.clone(), &credit)?;
+    util::add_funds(deps.storage, None, Denom::Native(cfg.native_token), amount)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "deposit_tx_fee")
+        .add_attribute("address", info.sender)
+        .add_attribute("credit", credit)
+    )
+}
+
+pub fn execute_withdraw_tx_fee_credit(
+    deps: DepsMut,
+    info: MessageInfo,
+    amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let cfg = CONFIG.load(deps.storage)?;
+
+    let credit = FEE_CREDIT_MAP.may_load(deps.storage, info.sender.clone())?.unwrap_or_default();
+    let amount = amount.unwrap_or(credit);
+    if amount.is_zero() || amount > credit {
+        return Err(ContractError::InsufficientCw20 {  });
     }
-    collection.users = new_users;
-    COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
+    FEE_CREDIT_MAP.save(deps.storage, info.sender.clone(), &(credit - amount))?;
+    let denom = Denom::Native(cfg.native_token);
+    util::sub_funds(deps.storage, None, &denom, amount)?;
+
+    let msg = util::transfer_token_message(denom, amount, info.sender.clone())?;
+
     Ok(Response::new()
-        .add_messages(msgs)
-        .add_attribute("action", "nft staking")
+        .add_message(msg)
+        .add_attribute("action", "withdraw_tx_fee_credit")
+        .add_attribute("address", info.sender)
+        .add_attribute("amount", amount)
+        .add_attribute("credit", credit - amount)
     )
-    
 }
+
+/// NFTs sent through cw721 `SendNft` carry no funds, so the collection's
+/// `tx_fee` is taken from the staker's prepaid credit (see `DepositTxFee`).
+/// Credit is held in the native token, so collections with another fee denom
+/// can't be staked into this way.
+fn take_tx_fee_credit(
+    storage: &mut dyn Storage,
+    cfg: &Config,
+    collection: &Collection,
+    address: Addr,
+) -> Result<Uint128, ContractError> {
+    let fee_denom = util::fee_denom(cfg, collection);
+    if fee_denom != Denom::Native(cfg.native_token.clone()) {
+        return Err(ContractError::InvalidDenom {  });
+    }
+    let tx_fee = util::tx_fee(cfg, collection);
+    if tx_fee.is_zero() {
+        return Ok(Uint128::zero());
+    }
+
+    let credit = FEE_CREDIT_MAP.may_load(storage, address.clone())?.unwrap_or_default();
+    if credit < tx_fee {
+        return Err(ContractError::InsufficientCw20 {  });
+    }
+    FEE_CREDIT_MAP.save(storage, address, &(credit - tx_fee))?;
+    util::sub_funds(storage, None, &fee_denom, tx_fee)?;
+    Ok(tx_fee)
+}
+
 pub fn execute_receive_nft(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     wrapper: Cw721ReceiveMsg
 ) -> Result<Response, ContractError> {
+    if !COLLECTION_MAP.has(deps.storage, info.sender.clone()) {
+        return Err(ContractError::InvalidCw721Token {  });
+    }
+    util::check_not_paused(deps.storage, Some(info.sender.clone()), |pause| pause.stake)?;
     util::check_enabled(deps.storage, info.sender.clone())?;
+    util::check_airdrop_start(deps.storage, info.sender.clone())?;
 
+    let cfg = CONFIG.load(deps.storage)?;
+    let collection = COLLECTION_MAP.load(deps.storage, info.sender.clone())?;
     let msg: NftReceiveMsg = from_binary(&wrapper.msg)?;
+    let stake_nft_id = wrapper.token_id.clone();
+    let user_addr = deps.api.addr_validate(wrapper.sender.as_str())?;
 
-    match msg {
+    let (collection_address, tier, tx_fee) = match msg {
         NftReceiveMsg::Stake {
             sender,
             token_id,
-            collection_address
+            collection_address,
+            tier,
         } => {
-            util::check_airdrop_start(deps.storage, collection_address.clone())?;
-        
-            if info.sender.clone() != collection_address.clone() {
-                return Err(crate::ContractError::InvalidCw721Token {  });
-            }
-        
-            let stake_nft_id = wrapper.token_id.clone();
-            let user_addr = deps.api.addr_validate(wrapper.sender.as_str())?;
-
             if (sender != user_addr) || (token_id != stake_nft_id) {
                 return Err(ContractError::InvalidCw721Msg {  });
             }
+            let tx_fee = take_tx_fee_credit(deps.storage, &cfg, &collection, user_addr.clone())?;
+            (collection_address, tier, tx_fee)
+        },
+        NftReceiveMsg::StakeBatch {
+            sender,
+            token_ids,
+            collection_address,
+            tier,
+        } => {
+            if sender != user_addr || !token_ids.contains(&stake_nft_id) {
+                return Err(ContractError::InvalidCw721Msg {  });
+            }
 
-            let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
-            let duration = collection.duration;
-            let _nftinfo = NftInfo {
-                nft_id: stake_nft_id.clone(),
-                lock_time: duration+env.block.time.seconds(),
-                airdrop: false,
-                collection_address: collection.address.clone()
-            };
-            
-            let _userinfo = UserInfo {
-                address: user_addr.clone(),
-                staked_nfts: vec![_nftinfo.clone()],
-                total_earnd: Uint128::zero(),
-                claimable: Uint128::zero(),
-                earn_infos: vec![],
-            };
-            let mut new_users = collection.users.clone();
-            let mut find = false;
-            for (index, user_info) in new_users.iter().enumerate() {
-                if user_info.address == user_addr.clone() {
-                    let mut _userinfo2 = user_info.clone();
-                    _userinfo2.staked_nfts.push(_nftinfo.clone());
-                    new_users[index] = _userinfo2;
-                    find = true;
-                    break;
+            // Every SendNft of the batch carries the same hook message. The first
+            // token pays the tx_fee and opens the batch for the rest of the block.
+            let batch_key = (info.sender.clone(), user_addr.clone());
+            let mut tx_fee = Uint128::zero();
+            if token_ids[0] == stake_nft_id {
+                tx_fee = take_tx_fee_credit(deps.storage, &cfg, &collection, user_addr.clone())?;
+                RECEIVE_BATCH_MAP.save(deps.storage, batch_key, &ReceiveBatch {
+                    height: env.block.height,
+                    token_ids,
+                })?;
+            } else {
+                match RECEIVE_BATCH_MAP.may_load(deps.storage, batch_key)? {
+                    Some(batch) if batch.height == env.block.height && batch.token_ids == token_ids => {},
+                    _ => return Err(ContractError::InvalidCw721Msg {  }),
                 }
             }
-            if !find {
-                new_users.push(_userinfo);
-            }
-            collection.users = new_users;
-            COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
-
-            Ok(Response::new()
-                .add_attribute("action", "execute_stake")
-                .add_attribute("collection_address", collection.address.clone())
-                .add_attribute("nft_id", stake_nft_id.clone())
-            )
+            (collection_address, tier, tx_fee)
         }
+    };
+
+    if info.sender != collection_address {
+        return Err(crate::ContractError::InvalidCw721Token {  });
     }
+
+    save_staked_nfts(deps.storage, env.block.time.seconds(), &collection, user_addr, vec![stake_nft_id.clone()], tier)?;
+    let fee_msgs = util::route_fee(deps.storage, &cfg, &collection, util::fee_denom(&cfg, &collection), tx_fee, 100 - collection.tx_fee_percent)?;
+
+    Ok(Response::new()
+        .add_messages(fee_msgs)
+        .add_attribute("action", "execute_stake")
+        .add_attribute("collection_address", collection.address)
+        .add_attribute("nft_id", stake_nft_id)
+    )
 }
 
 pub fn execute_restake(
@@ -708,60 +1553,82 @@ pub fn execute_restake(
     info: MessageInfo,
     collection_address: Addr,
     token_id: Vec<String>,
+    tier: Option<u32>,
+) -> Result<Response, ContractError> {
+    let fee_paid = util::native_fee_paid(deps.storage, &info, collection_address.clone())?;
+    restake_nfts(deps, env, info.sender, collection_address, token_id, tier, fee_paid)
+}
+
+fn restake_nfts(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    collection_address: Addr,
+    token_id: Vec<String>,
+    tier: Option<u32>,
+    fee_paid: Uint128,
 ) -> Result<Response, ContractError> {
+    util::check_not_paused(deps.storage, Some(collection_address.clone()), |pause| pause.stake)?;
     util::check_enabled(deps.storage, collection_address.clone())?;
     util::check_airdrop_start(deps.storage, collection_address.clone())?;
 
     let cfg = CONFIG.load(deps.storage)?;
-    let fee_amount = match must_pay(&info, &cfg.native_token) {
-        Ok(it) => it,
-        Err(_err) => return Err(ContractError::InsufficientCw20 {  }),
-    }.u128();
-
-    if fee_amount < u128::from(cfg.tx_fee) {
+    let collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
+    let tx_fee = util::tx_fee(&cfg, &collection);
+    if fee_paid < tx_fee {
         return Err(ContractError::InsufficientCw20 {  })
     }
 
-    let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
-    let user_index = collection.users.iter().position(|user_info| user_info.address == info.sender).unwrap_or(usize::MAX);
-    
-    if user_index == usize::MAX {
-        return Err(ContractError::NoStakedNft {  });
+    let mut response = Response::new();
+    if let Some((refund_msg, refund)) = util::refund_excess_fee(util::fee_denom(&cfg, &collection), fee_paid, tx_fee, sender.clone())? {
+        response = response
+            .add_message(refund_msg)
+            .add_attribute("refund", refund);
     }
+    let fee_msgs = util::route_fee(deps.storage, &cfg, &collection, util::fee_denom(&cfg, &collection), tx_fee, 100 - collection.tx_fee_percent)?;
+    response = response.add_messages(fee_msgs);
 
-    let mut userinfo = collection.users[user_index].clone();
-
-    if userinfo.staked_nfts.is_empty() {
-        return Err(ContractError::NoStakedNft {  });
-    }
+    let lock_tier = util::get_lock_tier(&collection, tier)?;
+    let multiplier = lock_tier.as_ref().map_or(0, |lock_tier| lock_tier.multiplier);
+    let mut shares_added = Uint128::zero();
+    let mut shares_removed = Uint128::zero();
     
     for nft_id in token_id.iter() {
-        let index = userinfo.staked_nfts.iter().position(|nft| nft.nft_id == nft_id.clone()).unwrap_or(usize::MAX);
-        if index == usize::MAX {
-            return Err(ContractError::NoStakedNft {  });
-        }
-        let mut nftinfo: NftInfo = userinfo.staked_nfts[index].clone();
-        if nftinfo.collection_address != collection.address {
-            return Err(ContractError::InvalidCw721Msg {  });
-        }
+        let mut nftinfo = match staked_nft_map().may_load(deps.storage, (collection_address.clone(), nft_id.clone()))? {
+            Some(nftinfo) if nftinfo.owner == sender => nftinfo,
+            _ => return Err(ContractError::NoStakedNft {  }),
+        };
 
-        if nftinfo.lock_time > env.block.time.seconds() {
+        if util::effective_lock_time(deps.storage, &collection, &nftinfo, env.block.time.seconds())? > env.block.time.seconds() {
             continue;
         }
 
-        nftinfo.lock_time = env.block.time.seconds() + collection.duration;
+        // The new lock period runs on the chosen tier.
+        shares_removed += nftinfo.share();
+        nftinfo.tier = tier;
+        nftinfo.multiplier = multiplier;
+        nftinfo.lock_tier = lock_tier.clone();
+        shares_added += nftinfo.share();
+
+        nftinfo.lock_time = env.block.time.seconds() + util::lock_duration(&collection, lock_tier.as_ref());
         nftinfo.airdrop = false;
-        userinfo.staked_nfts[index] = nftinfo;
-       
+        staked_nft_map().save(deps.storage, (collection_address.clone(), nft_id.clone()), &nftinfo)?;
     }
 
-    collection.users[user_index] = userinfo;
-
-    COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
+    if shares_added != shares_removed {
+        util::update_user_shares(
+            deps.storage, 
+            env.block.time.seconds(), 
+            collection_address, 
+            sender, 
+            shares_added, 
+            shares_removed
+        )?;
+    }
 
-    Ok(Response::new()
+    Ok(response
         .add_attribute("action", "restake")
-        .add_attribute("collection_address", collection.address.clone())
+        .add_attribute("collection_address", collection.address)
     )
     
 }
@@ -774,97 +1641,157 @@ pub fn execute_transfer_nft(
     recipient: String,
 ) -> Result<Response, ContractError> {
     util::check_owner(deps.storage, info.sender.clone())?;
-    let mut msgs:Vec<CosmosMsg> = vec![];
-    msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: collection_address.clone().to_string(),
+    let msgs: Vec<CosmosMsg> = vec![CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: collection_address.to_string(),
         msg: to_binary(&Cw721ExecuteMsg::TransferNft {
             token_id: nft_id,
-            recipient: recipient.clone().into()
+            recipient
         })?,
         funds: vec![],
-    }));
+    })];
     Ok(Response::new()
         .add_messages(msgs)
-        .add_attribute("action", "transfer nft")
+        .add_attribute("action", "transfer nft")
+    )
+}
+pub fn execute_unstake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_address: Addr,
+    token_id: Vec<String>
+) -> Result<Response, ContractError> {
+    let fee_paid = util::native_fee_paid(deps.storage, &info, collection_address.clone())?;
+    unstake_nfts(deps, env, info.sender, collection_address, token_id, fee_paid)
+}
+
+fn unstake_nfts(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    collection_address: Addr,
+    token_id: Vec<String>,
+    fee_paid: Uint128,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
+    let emergency = util::load_pause(deps.storage, Some(collection_address.clone()))?.emergency_unstake;
+    // Emergency unstake skips the unbonding period as well as the fee.
+    let unbonding = !emergency && collection.unbonding_duration > 0;
+    let unbonding_key = (collection_address.clone(), sender.clone());
+    let mut queue = UNBONDING_MAP.may_load(deps.storage, unbonding_key.clone())?.unwrap_or_default();
+    
+    let mut msgs:Vec<CosmosMsg> = vec![];
+    let mut total_fee = Uint128::zero();
+    let mut shares = Uint128::zero();
+    for nft_id in token_id.iter() {
+        let nftinfo = match staked_nft_map().may_load(deps.storage, (collection_address.clone(), nft_id.clone()))? {
+            Some(nftinfo) if nftinfo.owner == sender => nftinfo,
+            _ => return Err(ContractError::NoStakedNft {  }),
+        };
+
+        let lock_time = util::effective_lock_time(deps.storage, &collection, &nftinfo, env.block.time.seconds())?;
+        if !emergency {
+            total_fee += util::early_unstake_fee(&collection, &nftinfo, lock_time, env.block.time.seconds());
+        }
+
+        if unbonding {
+            queue.push(UnbondingNft {
+                nft_id: nft_id.clone(),
+                release_at: env.block.time.seconds() + collection.unbonding_duration,
+            });
+        } else {
+            msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: collection.address.clone().to_string(),
+                msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+                    token_id: nft_id.clone(),
+                    recipient: sender.clone().into()
+                })?,
+                funds: vec![],
+            }));
+        }
+        
+        shares += nftinfo.share();
+        staked_nft_map().remove(deps.storage, (collection_address.clone(), nft_id.clone()))?;
+    }
+
+    if unbonding {
+        UNBONDING_MAP.save(deps.storage, unbonding_key, &queue)?;
+    }
+
+    util::update_user_shares(
+        deps.storage, 
+        env.block.time.seconds(), 
+        collection_address, 
+        sender.clone(), 
+        Uint128::zero(), 
+        shares
+    )?;
+    
+	if total_fee > Uint128::zero() {
+		if fee_paid >= total_fee {
+			let fee_msgs = util::route_fee(deps.storage, &cfg, &collection, util::fee_denom(&cfg, &collection), total_fee, collection.unstaking_fee_percent)?;
+			msgs.extend(fee_msgs);
+		} else {
+			return Err(ContractError::Locktime {  });
+		}
+	}
+
+    let mut response = Response::new();
+    if let Some((refund_msg, refund)) = util::refund_excess_fee(util::fee_denom(&cfg, &collection), fee_paid, total_fee, sender)? {
+        msgs.push(refund_msg);
+        response = response.add_attribute("refund", refund);
+    }
+
+    Ok(response
+        .add_messages(msgs)
+        .add_attribute("action", "unstake")
+        .add_attribute("emergency", emergency.to_string())
+        .add_attribute("unbonding", unbonding.to_string())
     )
+
 }
-pub fn execute_unstake(
+
+pub fn execute_withdraw_unbonded(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     collection_address: Addr,
-    token_id: Vec<String>
 ) -> Result<Response, ContractError> {
-    let cfg = CONFIG.load(deps.storage)?;
-    let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
-    let user_index = collection.users.iter().position(|user_info| user_info.address == info.sender).unwrap_or(usize::MAX);
-    
-    if user_index == usize::MAX {
-        return Err(ContractError::NoStakedNft {  });
+    let key = (collection_address.clone(), info.sender.clone());
+    let queue = UNBONDING_MAP.may_load(deps.storage, key.clone())?.unwrap_or_default();
+    let (released, pending): (Vec<UnbondingNft>, Vec<UnbondingNft>) = queue
+        .into_iter()
+        .partition(|unbonding| unbonding.release_at <= env.block.time.seconds());
+
+    if released.is_empty() {
+        return Err(ContractError::NoUnbondedNft {  });
     }
 
-    let mut userinfo = collection.users[user_index].clone();
-
-    if userinfo.staked_nfts.is_empty() {
-        return Err(ContractError::NoStakedNft {  });
+    if pending.is_empty() {
+        UNBONDING_MAP.remove(deps.storage, key);
+    } else {
+        UNBONDING_MAP.save(deps.storage, key, &pending)?;
     }
-    
-    let mut msgs:Vec<CosmosMsg> = vec![];
-    let mut total_fee = Uint128::zero();
-    for nft_id in token_id.iter() {
-        let index = userinfo.staked_nfts.iter().position(|nft| nft.nft_id == nft_id.clone()).unwrap_or(usize::MAX);
-        if index == usize::MAX {
-            return Err(ContractError::NoStakedNft {  });
-        }
-        let nftinfo: NftInfo = userinfo.staked_nfts[index].clone();
-        if nftinfo.collection_address != collection.address {
-            return Err(ContractError::InvalidCw721Token {  });
-        }
-
-        if (nftinfo.lock_time > env.block.time.seconds()) && (collection.unstaking_fee > Uint128::zero()) {
-            total_fee += collection.unstaking_fee;
-        }
 
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    for unbonding in released.iter() {
         msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: collection.address.clone().to_string(),
+            contract_addr: collection_address.to_string(),
             msg: to_binary(&Cw721ExecuteMsg::TransferNft {
-                token_id: nft_id.clone(),
+                token_id: unbonding.nft_id.clone(),
                 recipient: info.sender.clone().into()
             })?,
             funds: vec![],
         }));
-        
-        userinfo.staked_nfts.remove(index);
     }
-    
-	if total_fee > Uint128::zero() {
-		let receive_fee = match must_pay(&info, &cfg.native_token) {
-			Ok(it) => it,
-			Err(_err) => return Err(ContractError::Locktime {  }),
-		}.u128();
-
-		if receive_fee >= u128::from(total_fee) {
-			let owner_fee = u128::from(total_fee) * u128::from(collection.unstaking_fee_percent) / 100u128;
-            if owner_fee > 0u128 {
-                let owner_fee_msg = util::transfer_token_message(Denom::Native(cfg.native_token.clone()), Uint128::from(owner_fee), cfg.fee_address.clone())?;
-                msgs.push(owner_fee_msg);
-            }
-			let fee_msg = util::transfer_token_message(Denom::Native(cfg.native_token.clone()), total_fee - Uint128::from(owner_fee), collection.fee_address.clone())?;
-			msgs.push(fee_msg);
-		} else {
-			return Err(ContractError::Locktime {  });
-		}
-	}
-
-    collection.users[user_index] = userinfo;
-
-    COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
 
     Ok(Response::new()
         .add_messages(msgs)
-        .add_attribute("action", "unstake")
+        .add_attribute("action", "withdraw_unbonded")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("count", released.len().to_string())
     )
-
 }
 
 pub fn execute_claim(
@@ -872,74 +1799,168 @@ pub fn execute_claim(
     env: Env,
     info: MessageInfo,
     collection_address: Addr,
-    cw20_address: Addr,
+    denom: Denom,
+    owner: Option<Addr>,
+    recipient: Option<Addr>,
 ) -> Result<Response, ContractError> {
-    let cfg = CONFIG.load(deps.storage)?;
-    let mut collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
-    let user_index = collection.users.iter().position(|user_info| user_info.address == info.sender).unwrap_or(usize::MAX);
-    
-    if user_index == usize::MAX {
-        return Err(ContractError::NoStakedNft {  });
+    util::check_not_paused(deps.storage, Some(collection_address.clone()), |pause| pause.claim)?;
+    let (owner, recipient) = util::claim_parties(deps.storage, &env.block, info.sender, owner, recipient)?;
+
+    // Users who fully unstaked can still claim what they settled on the way out.
+    if !user_map().has(deps.storage, (collection_address.clone(), owner.clone())) {
+        return Err(ContractError::NoReward {  });
+    }
+
+    let collection = COLLECTION_MAP.load(deps.storage, collection_address)?;
+    let rewards = util::take_claimable(deps.storage, env.block.time.seconds(), &collection, owner.clone(), Some(&denom))?;
+    match rewards.first() {
+        Some((_, amount, vested)) => {
+            let (amount, vested) = (*amount, *vested);
+            let mut msgs: Vec<CosmosMsg> = vec![];
+            if !vested {
+                msgs.push(util::transfer_token_message(denom.clone(), amount, recipient.clone())?);
+            }
+                
+            Ok(Response::new()
+                .add_messages(msgs)
+                .add_attribute("action", "claim")
+                .add_attribute("vested", vested.to_string())
+                .add_attribute("address", owner.to_string())
+                .add_attribute("recipient", recipient.to_string())
+                .add_attribute("denom", util::denom_string(&denom))
+                .add_attribute("claimed_amount", amount)
+            )
+        },
+        _ => {
+            Err(ContractError::NoReward {  })
+        }
     }
+}
+
+/// Claims every token from every collection the user is in, or only from
+/// `collection_addresses`. Paused collections and ones the user was never in
+/// are skipped, and transfers are merged per denom.
+pub fn execute_claim_all(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_addresses: Option<Vec<Addr>>,
+    owner: Option<Addr>,
+    recipient: Option<Addr>,
+) -> Result<Response, ContractError> {
+    let (owner, recipient) = util::claim_parties(deps.storage, &env.block, info.sender, owner, recipient)?;
+    let collection_addresses = match collection_addresses {
+        Some(collection_addresses) => collection_addresses,
+        None => user_map()
+            .idx
+            .address
+            .prefix(owner.clone())
+            .keys(deps.storage, None, None, Order::Ascending)
+            .map(|key| key.map(|(collection_address, _)| collection_address))
+            .collect::<StdResult<Vec<Addr>>>()?,
+    };
 
-    let mut userinfo = collection.users[user_index].clone();
+    let mut transfers: Vec<(Denom, Uint128)> = vec![];
+    let mut events: Vec<Event> = vec![];
+    for collection_address in collection_addresses {
+        if util::check_not_paused(deps.storage, Some(collection_address.clone()), |pause| pause.claim).is_err()
+            || !user_map().has(deps.storage, (collection_address.clone(), owner.clone())) {
+            continue;
+        }
+
+        let collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
+        let rewards = util::take_claimable(deps.storage, env.block.time.seconds(), &collection, owner.clone(), None)?;
+        for (denom, amount, vested) in rewards {
+            events.push(Event::new("claim")
+                .add_attribute("collection_address", collection_address.clone())
+                .add_attribute("denom", util::denom_string(&denom))
+                .add_attribute("claimed_amount", amount)
+                .add_attribute("vested", vested.to_string())
+            );
+            if vested {
+                continue;
+            }
+            match transfers.iter_mut().find(|(transfer_denom, _)| *transfer_denom == denom) {
+                Some((_, total)) => *total += amount,
+                None => transfers.push((denom, amount)),
+            }
+        }
+    }
 
-    if userinfo.staked_nfts.is_empty() {
-        return Err(ContractError::NoStakedNft {  });
+    if events.is_empty() {
+        return Err(ContractError::NoReward {  });
     }
-    
-    let reward_msg;
-    let amount;
-    if cw20_address.to_string().len() == 0 {
-        if userinfo.claimable == Uint128::zero() {
-            return Err(ContractError::NoReward {  });
+
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    for (denom, amount) in transfers {
+        msgs.push(util::transfer_token_message(denom, amount, recipient.clone())?);
+    }
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_events(events)
+        .add_attribute("action", "claim_all")
+        .add_attribute("address", owner.to_string())
+        .add_attribute("recipient", recipient.to_string())
+    )
+}
+
+pub fn execute_withdraw_vested(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_address: Addr,
+    denom: Option<Denom>,
+    owner: Option<Addr>,
+    recipient: Option<Addr>,
+) -> Result<Response, ContractError> {
+    util::check_not_paused(deps.storage, Some(collection_address.clone()), |pause| pause.claim)?;
+    let (owner, recipient) = util::claim_parties(deps.storage, &env.block, info.sender, owner, recipient)?;
+
+    let key = (collection_address.clone(), owner.clone());
+    let mut vestings = VESTING_MAP.may_load(deps.storage, key.clone())?.unwrap_or_default();
+    let now = env.block.time.seconds();
+
+    let mut amounts: Vec<(Denom, Uint128)> = vec![];
+    for vesting in vestings.iter_mut() {
+        if denom.as_ref().is_some_and(|denom| *denom != vesting.denom) {
+            continue;
         }
-        amount = userinfo.claimable.clone();
-        if util::get_token_amount(deps.querier, Denom::Native(cfg.native_token.clone()), env.clone().contract.address.clone())? < amount {
-            return Err(crate::ContractError::InsufficientCw20 {  });
+        let amount = vesting.vested(now) - vesting.released;
+        if amount.is_zero() {
+            continue;
         }
-        reward_msg = util::transfer_token_message(Denom::Native(cfg.native_token.clone()), amount, info.sender.clone())?;
-        userinfo.total_earnd += amount;
-        userinfo.claimable = Uint128::zero();
-
-        collection.users[user_index] = userinfo;
-
-        COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
-            
-        return Ok(Response::new()
-            .add_message(reward_msg)
-            .add_attribute("action", "claim")
-            .add_attribute("address", info.sender.clone().to_string())
-            .add_attribute("claimed_amount", amount)
-        );
-    } else {
-        let index = userinfo.earn_infos.iter().position(|earn_info| earn_info.cw20_address == cw20_address);
-        match index {
-            Some(index) => {
-                amount = userinfo.earn_infos[index].claimable.clone();
-                if util::get_token_amount(deps.querier, Denom::Cw20(collection.cw20_address.clone()), env.clone().contract.address.clone())? < amount {
-                    return Err(crate::ContractError::InsufficientCw20 {  });
-                }
-                reward_msg = util::transfer_token_message(Denom::Cw20(collection.cw20_address.clone()), amount, info.sender.clone())?;
-                userinfo.earn_infos[index].total_earned += amount;
-                userinfo.earn_infos[index].claimable = Uint128::zero();
-
-                collection.users[user_index] = userinfo;
-
-                COLLECTION_MAP.save(deps.storage, collection_address.clone(), &collection)?;
-                    
-                return Ok(Response::new()
-                    .add_message(reward_msg)
-                    .add_attribute("action", "claim")
-                    .add_attribute("address", info.sender.clone().to_string())
-                    .add_attribute("claimed_amount", amount.clone())
-                );
-            },
-            None => {
-                return Err(ContractError::NoReward {  });
-            }
+        vesting.released += amount;
+        match amounts.iter_mut().find(|(denom, _)| *denom == vesting.denom) {
+            Some((_, total)) => *total += amount,
+            None => amounts.push((vesting.denom.clone(), amount)),
         }
     }
+
+    if amounts.is_empty() {
+        return Err(ContractError::NoReward {  });
+    }
+
+    vestings.retain(|vesting| vesting.released < vesting.amount);
+    if vestings.is_empty() {
+        VESTING_MAP.remove(deps.storage, key);
+    } else {
+        VESTING_MAP.save(deps.storage, key, &vestings)?;
+    }
+
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    let mut response = Response::new()
+        .add_attribute("action", "withdraw_vested")
+        .add_attribute("collection_address", collection_address.clone())
+        .add_attribute("address", owner.to_string())
+        .add_attribute("recipient", recipient.to_string());
+    for (denom, amount) in amounts {
+        util::sub_funds(deps.storage, Some(collection_address.clone()), &denom, amount)?;
+        msgs.push(util::transfer_token_message(denom.clone(), amount, recipient.clone())?);
+        response = response.add_attribute(util::denom_string(&denom), amount);
+    }
+
+    Ok(response.add_messages(msgs))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -947,13 +1968,36 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::GetConfig {} => to_binary(&query_config(deps)?),
         QueryMsg::GetCollection {collection_address} => to_binary(&query_collection(deps, env, collection_address)?),
-        QueryMsg::GetUserList {collection_address} => to_binary(&query_user_list(deps, env, collection_address)?),
-        QueryMsg::GetCollectionList {} => to_binary(&query_collection_list(deps)?),
-        QueryMsg::GetShownCollectionList {} => to_binary(&query_shown_collection_list(deps)?),
+        QueryMsg::GetUserList {collection_address, start_after, limit} => to_binary(&query_user_list(deps, env, collection_address, start_after, limit)?),
+        QueryMsg::GetCollectionList {start_after, limit} => to_binary(&query_collection_list(deps, start_after, limit)?),
+        QueryMsg::GetShownCollectionList {start_after, limit} => to_binary(&query_shown_collection_list(deps, start_after, limit)?),
         QueryMsg::GetTotalLocked {collection_address} => to_binary(&query_total_locked(deps, env, collection_address)?),
-        QueryMsg::GetStakedNfts { address, collection_address } => to_binary(&query_staked_nfts(deps, address, collection_address)?),
+        QueryMsg::GetStakedNfts { address, collection_address, start_after, limit } => to_binary(&query_staked_nfts(deps, env, address, collection_address, start_after, limit)?),
+        QueryMsg::GetVestingInfos { address, collection_address } => to_binary(&query_vesting_infos(deps, env, address, collection_address)?),
+        QueryMsg::Audit { start_after, limit } => to_binary(&query_audit(deps, env, start_after, limit)?),
+        QueryMsg::GetClaimOperators { owner, start_after, limit } => to_binary(&query_claim_operators(deps, owner, start_after, limit)?),
+        QueryMsg::GetWithdrawAddress { owner } => to_binary(&query_withdraw_address(deps, owner)?),
+        QueryMsg::GetFunds { collection_address } => to_binary(&query_funds(deps, collection_address)?),
+        QueryMsg::GetRevenue { collection_address } => to_binary(&query_revenue(deps, collection_address)?),
+        QueryMsg::GetUnstakeFee { collection_address, token_ids } => to_binary(&query_unstake_fee(deps, env, collection_address, token_ids)?),
+        QueryMsg::GetUnbondingNfts { address, collection_address } => to_binary(&query_unbonding_nfts(deps, env, address, collection_address)?),
         QueryMsg::GetAirdropInfos { address } => to_binary(&query_airdrop_infos(deps, address)?),
-        QueryMsg::GetEarnInfos { address, collection_address } => to_binary(&query_earn_infos(deps, address, collection_address)?),
+        QueryMsg::GetAutoRelock { address, collection_address } => to_binary(&query_auto_relock(deps, address, collection_address)?),
+        QueryMsg::GetEarnInfos { address, collection_address } => to_binary(&query_earn_infos(deps, env, address, collection_address)?),
+        QueryMsg::GetStreamInfo { collection_address } => to_binary(&query_stream_info(deps, env, collection_address)?),
+        QueryMsg::GetTxFeeCredit { address } => to_binary(&query_tx_fee_credit(deps, address)?),
+        QueryMsg::GetPendingOwner {} => to_binary(&query_pending_owner(deps)?),
+        QueryMsg::GetPendingCollectionOwner { collection_address } => to_binary(&query_pending_collection_owner(deps, collection_address)?),
+        QueryMsg::GetRoles { address } => to_binary(&query_roles(deps, address)?),
+        QueryMsg::GetRoleMembers { role, start_after, limit } => to_binary(&query_role_members(deps, role, start_after, limit)?),
+        QueryMsg::GetCollectionManagers { collection_address, start_after, limit } => to_binary(&query_collection_managers(deps, collection_address, start_after, limit)?),
+        QueryMsg::GetRarityWeights { collection_address, start_after, limit } => to_binary(&query_rarity_weights(deps, collection_address, start_after, limit)?),
+        QueryMsg::GetRarityRanges { collection_address, start_after, limit } => to_binary(&query_rarity_ranges(deps, collection_address, start_after, limit)?),
+        QueryMsg::GetTokenWeight { collection_address, token_id } => to_binary(&query_token_weight(deps, collection_address, token_id)?),
+        QueryMsg::GetMerkleAirdrop { airdrop_id } => to_binary(&MERKLE_AIRDROP_MAP.load(deps.storage, airdrop_id)?),
+        QueryMsg::GetMerkleAirdropList { start_after, limit } => to_binary(&query_merkle_airdrop_list(deps, start_after, limit)?),
+        QueryMsg::GetMerkleClaimed { airdrop_id, address } => to_binary(&query_merkle_claimed(deps, airdrop_id, address)?),
+        QueryMsg::GetPauseInfo { collection_address } => to_binary(&query_pause_info(deps, collection_address)?),
     }
 }
 
@@ -974,11 +2018,14 @@ pub fn query_collection(
     collection_address: Addr,
 ) -> StdResult<CollectionResponse> {
     let collection = COLLECTION_MAP.load(deps.storage, collection_address)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let fee_denom = util::fee_denom(&cfg, &collection);
+    let tx_fee = util::tx_fee(&cfg, &collection);
     Ok(CollectionResponse {
         address: collection.address,
         owner: collection.owner,
-        total_airdrop: collection.total_airdrop,
-        airdropable: collection.airdropable,
+        airdrop_infos: collection.airdrop_infos,
+        native_denoms: collection.native_denoms,
         duration: collection.duration,
         started_at: collection.started_at,
         fee_address: collection.fee_address,
@@ -988,6 +2035,15 @@ pub fn query_collection(
         is_enabled: collection.is_enabled,
         unstaking_fee: collection.unstaking_fee,
         unstaking_fee_percent: collection.unstaking_fee_percent,
+        cw20_denoms: collection.cw20_denoms,
+        airdrop_mode: collection.airdrop_mode,
+        vesting: collection.vesting,
+        lock_tiers: collection.lock_tiers,
+        unbonding_duration: collection.unbonding_duration,
+        fee_curve: collection.fee_curve,
+        fee_denom,
+        tx_fee,
+        tx_fee_percent: collection.tx_fee_percent,
         server_time: env.block.time.seconds()
     })
 }
@@ -995,44 +2051,45 @@ pub fn query_collection(
 pub fn query_user_list(
     deps: Deps, 
     _env: Env, 
-    collection_address: Addr
+    collection_address: Addr,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
 ) -> StdResult<UserListResponse> {
-    let result = util::get_all_addresses(deps.storage, collection_address.clone());
-
-    match result {
-        Ok(addresses) => {
-            let mut result = Vec::new();
-            for address in addresses {
-                let nfts = query_staked_nfts(deps, address.clone(), collection_address.clone())?;
-                if nfts.nft_maps.len() > 0 {
-                    result.push(address.clone())
-                }
-            }
-            Ok(UserListResponse {
-                addresses: result
-            })
-        },
-        Err(_error) => {
-            Ok(UserListResponse {
-                addresses: Vec::new()
-            })
-        }
-    }
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let (addresses, next_start_after) = util::get_addresses(deps.storage, collection_address, start_after, limit)?;
+
+    Ok(UserListResponse {
+        addresses,
+        next_start_after
+    })
 }
 
-pub fn query_collection_list(deps: Deps) -> StdResult<CollectionListResponse> {
-    let collections = util::get_all_collections(deps.storage);
+pub fn query_collection_list(
+    deps: Deps,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<CollectionListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let collections = util::get_collections(deps.storage, start_after, limit)?;
+    let next_start_after = if collections.len() == limit { collections.last().cloned() } else { None };
 
     Ok(CollectionListResponse {
-        collections
+        collections,
+        next_start_after
     })
 }
 
-pub fn query_shown_collection_list(deps: Deps) -> StdResult<CollectionListResponse> {
-    let collections = util::get_all_shown_collections(deps.storage);
+pub fn query_shown_collection_list(
+    deps: Deps,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<CollectionListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let (collections, next_start_after) = util::get_shown_collections(deps.storage, start_after, limit)?;
 
     Ok(CollectionListResponse {
-        collections
+        collections,
+        next_start_after
     })
 }
 
@@ -1041,16 +2098,18 @@ pub fn query_total_locked(
     env: Env,
     collection_address: Addr,
 ) -> StdResult<TotalLockedResponse> {
-    let nft_count = util::get_in_locktime_nft_count(deps.storage, env.block.clone(), collection_address.clone());
-    match nft_count {
-        Ok(nft_count) => {
+    let totals = util::get_in_locktime_totals(deps.storage, env.block.clone(), collection_address.clone());
+    match totals {
+        Ok((count, shares)) => {
             Ok(TotalLockedResponse {
-                count: nft_count.clone()
+                count,
+                shares
             })
         },
         Err(_error) => {
             Ok(TotalLockedResponse {
-                count: Uint128::zero()
+                count: Uint128::zero(),
+                shares: Uint128::zero()
             })
         }
     }
@@ -1058,54 +2117,263 @@ pub fn query_total_locked(
 
 pub fn query_staked_nfts(
     deps: Deps, 
+    env: Env,
     address: Addr,
     collection_address: Addr,
+    start_after: Option<String>,
+    limit: Option<u32>,
 ) -> StdResult<StakedNftsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
     let collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
-    let user_index = collection.users.iter().position(|user_info| user_info.address == address).unwrap_or(usize::MAX);
-    
-    if user_index == usize::MAX {
-        return Ok(StakedNftsResponse { nft_maps: vec![]})
-    }
-
-    let userinfo = collection.users[user_index].clone();
-
-    let mut nft_maps : Vec<StakedNftResponse> = Vec::new();
-    for nft in userinfo.staked_nfts {
-        if nft.collection_address != collection_address {
-            continue;
-        }
+    let mut nft_maps: Vec<StakedNftResponse> = vec![];
+    for nft in util::get_staked_nfts(deps.storage, collection_address, address, start_after, limit)? {
         nft_maps.push(StakedNftResponse { 
+            lock_time: util::effective_lock_time(deps.storage, &collection, &nft, env.block.time.seconds())?,
+            auto_relock: util::is_auto_relock(deps.storage, &nft)?,
+            tier: nft.tier,
+            multiplier: nft.multiplier,
             nft_id: nft.nft_id, 
             airdrop: nft.airdrop, 
-            lock_time: nft.lock_time,
-        })
+            weight: nft.weight.max(1),
+        });
     }
-    Ok(StakedNftsResponse { nft_maps })
+    let next_start_after = if nft_maps.len() == limit { nft_maps.last().map(|nft| nft.nft_id.clone()) } else { None };
+
+    Ok(StakedNftsResponse { 
+        nft_maps,
+        next_start_after
+    })
+}
+
+pub fn query_auto_relock(
+    deps: Deps, 
+    address: Addr,
+    collection_address: Addr
+) -> StdResult<AutoRelockResponse> {
+    let enabled = AUTO_RELOCK_MAP.may_load(deps.storage, (collection_address, address))?.unwrap_or(false);
+    Ok(AutoRelockResponse { enabled })
 }
 
 pub fn query_earn_infos(
     deps: Deps, 
+    env: Env,
     address: Addr,
     collection_address: Addr
 ) -> StdResult<EarnInfosResponse> {
-    let collection = COLLECTION_MAP.load(deps.storage, collection_address)?;
-    let user_index = collection.users.iter().position(|user_info| user_info.address == address).unwrap_or(usize::MAX);
-    
-    if user_index == usize::MAX {
-        return  Ok(EarnInfosResponse {
-            total_earned: Uint128::zero(),
-            claimable: Uint128::zero(),
-            earn_infos: vec![],
-        })
+    match user_map().may_load(deps.storage, (collection_address.clone(), address))? {
+        Some(mut userinfo) => {
+            let mut stream = util::load_stream(deps.storage, collection_address)?;
+            util::accrue_stream(&mut stream, env.block.time.seconds());
+            util::settle_stream_reward(&mut stream, &mut userinfo)?;
+
+            Ok(EarnInfosResponse {
+                earn_infos: userinfo.earn_infos,
+            })
+        },
+        None => {
+            Ok(EarnInfosResponse {
+                earn_infos: vec![],
+            })
+        }
+    }
+}
+
+pub fn query_vesting_infos(
+    deps: Deps, 
+    env: Env,
+    address: Addr,
+    collection_address: Addr
+) -> StdResult<VestingInfosResponse> {
+    let now = env.block.time.seconds();
+    let vestings = VESTING_MAP.may_load(deps.storage, (collection_address, address))?.unwrap_or_default();
+
+    let mut balances: Vec<VestingBalance> = vec![];
+    for vesting in vestings.iter() {
+        let vested = vesting.vested(now);
+        let index = match balances.iter().position(|balance| balance.denom == vesting.denom) {
+            Some(index) => index,
+            None => {
+                balances.push(VestingBalance {
+                    denom: vesting.denom.clone(),
+                    locked: Uint128::zero(),
+                    unlocked: Uint128::zero(),
+                });
+                balances.len() - 1
+            }
+        };
+        balances[index].locked += vesting.amount - vested;
+        balances[index].unlocked += vested - vesting.released;
+    }
+
+    Ok(VestingInfosResponse {
+        vestings,
+        balances,
+        server_time: now,
+    })
+}
+
+pub fn query_audit(
+    deps: Deps, 
+    env: Env,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<AuditResponse> {
+    let mut denoms: Vec<DenomAudit> = vec![];
+    for fund in ALLOCATED_FUNDS.may_load(deps.storage)?.unwrap_or_default() {
+        // a denom whose balance can't be queried is reported, not fatal
+        let (balance, error) = match util::get_token_amount(deps.querier, fund.denom.clone(), env.contract.address.clone()) {
+            Ok(balance) => (Some(balance), None),
+            Err(err) => (None, Some(err.to_string())),
+        };
+        denoms.push(DenomAudit {
+            shortfall: balance.map(|balance| fund.amount.saturating_sub(balance)),
+            surplus: balance.map(|balance| balance.saturating_sub(fund.amount)),
+            denom: fund.denom,
+            liabilities: fund.amount,
+            balance,
+            error,
+        });
+    }
+
+    let limit = limit.unwrap_or(AUDIT_DEFAULT_LIMIT).min(AUDIT_MAX_LIMIT) as usize;
+    let addresses = util::get_collections(deps.storage, start_after, limit)?;
+    let next_start_after = if addresses.len() == limit { addresses.last().cloned() } else { None };
+
+    let mut collections: Vec<CollectionAudit> = vec![];
+    for collection_address in addresses {
+        let collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
+        let liabilities = util::collection_liabilities(deps.storage, &collection)?;
+        let ledger = COLLECTION_FUNDS_MAP.may_load(deps.storage, collection_address.clone())?.unwrap_or_default();
+        let amount_of = |funds: &[FundInfo], denom: &Denom| funds
+            .iter()
+            .filter(|fund| fund.denom == *denom)
+            .fold(Uint128::zero(), |total, fund| total + fund.amount);
+        let in_sync = liabilities.iter().chain(ledger.iter())
+            .all(|fund| amount_of(&liabilities, &fund.denom) == amount_of(&ledger, &fund.denom));
+        collections.push(CollectionAudit {
+            collection_address,
+            liabilities,
+            ledger,
+            in_sync,
+        });
+    }
+
+    Ok(AuditResponse {
+        denoms,
+        collections,
+        next_start_after,
+    })
+}
+
+pub fn query_claim_operators(
+    deps: Deps,
+    owner: Addr,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<ClaimOperatorsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let operators = util::get_claim_operators(deps.storage, owner, start_after, limit)?;
+    let next_start_after = if operators.len() == limit { operators.last().map(|(operator, _)| operator.clone()) } else { None };
+
+    Ok(ClaimOperatorsResponse {
+        operators: operators
+            .into_iter()
+            .map(|(operator, expires)| ClaimOperator { operator, expires })
+            .collect(),
+        next_start_after
+    })
+}
+
+pub fn query_withdraw_address(
+    deps: Deps,
+    owner: Addr,
+) -> StdResult<WithdrawAddressResponse> {
+    Ok(WithdrawAddressResponse {
+        address: WITHDRAW_ADDRESS_MAP.may_load(deps.storage, owner)?,
+    })
+}
+
+pub fn query_funds(
+    deps: Deps, 
+    collection_address: Option<Addr>,
+) -> StdResult<FundsResponse> {
+    let funds = match collection_address {
+        Some(collection_address) => COLLECTION_FUNDS_MAP.may_load(deps.storage, collection_address)?,
+        None => ALLOCATED_FUNDS.may_load(deps.storage)?,
+    };
+    Ok(FundsResponse {
+        funds: funds.unwrap_or_default(),
+    })
+}
+
+pub fn query_revenue(
+    deps: Deps, 
+    collection_address: Option<Addr>,
+) -> StdResult<RevenueResponse> {
+    if let Some(collection_address) = collection_address {
+        return Ok(RevenueResponse {
+            revenues: REVENUE_MAP.may_load(deps.storage, collection_address)?.unwrap_or_default(),
+        });
+    }
+
+    let mut revenues: Vec<RevenueInfo> = vec![];
+    for item in REVENUE_MAP.range(deps.storage, None, None, Order::Ascending) {
+        let (_, collection_revenues) = item?;
+        for collection_revenue in collection_revenues {
+            match revenues.iter_mut().find(|revenue| revenue.denom == collection_revenue.denom) {
+                Some(revenue) => {
+                    revenue.protocol += collection_revenue.protocol;
+                    revenue.collection += collection_revenue.collection;
+                },
+                None => revenues.push(collection_revenue),
+            }
+        }
+    }
+    Ok(RevenueResponse { revenues })
+}
+
+pub fn query_unstake_fee(
+    deps: Deps, 
+    env: Env,
+    collection_address: Addr,
+    token_ids: Vec<String>,
+) -> StdResult<UnstakeFeeResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let collection = COLLECTION_MAP.load(deps.storage, collection_address.clone())?;
+    let emergency = util::load_pause(deps.storage, Some(collection_address.clone()))?.emergency_unstake;
+    let now = env.block.time.seconds();
+
+    let mut fees: Vec<TokenFee> = vec![];
+    let mut total_fee = Uint128::zero();
+    for token_id in token_ids {
+        let nftinfo = staked_nft_map().load(deps.storage, (collection_address.clone(), token_id.clone()))?;
+        let fee = if emergency {
+            Uint128::zero()
+        } else {
+            let lock_time = util::effective_lock_time(deps.storage, &collection, &nftinfo, now)?;
+            util::early_unstake_fee(&collection, &nftinfo, lock_time, now)
+        };
+        total_fee += fee;
+        fees.push(TokenFee { token_id, fee });
     }
 
-    let userinfo = collection.users[user_index].clone();
+    Ok(UnstakeFeeResponse {
+        fees,
+        total_fee,
+        denom: util::fee_denom(&cfg, &collection),
+        server_time: now,
+    })
+}
 
-    Ok(EarnInfosResponse {
-        total_earned: userinfo.total_earnd.clone(),
-        claimable: userinfo.claimable.clone(),
-        earn_infos: userinfo.earn_infos.clone(),
+pub fn query_unbonding_nfts(
+    deps: Deps, 
+    env: Env,
+    address: Addr,
+    collection_address: Addr
+) -> StdResult<UnbondingNftsResponse> {
+    Ok(UnbondingNftsResponse {
+        unbonding: UNBONDING_MAP.may_load(deps.storage, (collection_address, address))?.unwrap_or_default(),
+        server_time: env.block.time.seconds(),
     })
 }
 
@@ -1118,17 +2386,197 @@ pub fn query_airdrop_infos(
     match collection {
         Ok(collection) => {
             Ok(AirdropInfosResponse {
-                total_airdrop: collection.total_airdrop.clone(),
-                airdropable: collection.airdropable.clone(),
-                airdrop_infos: collection.airdrop_infos.clone(),
+                airdrop_infos: collection.airdrop_infos,
             })
         },
         Err(_error) => {
             Ok(AirdropInfosResponse {
-                total_airdrop: Uint128::zero(),
-                airdropable: Uint128::zero(),
                 airdrop_infos: vec![],
             })
         }
     }
-}
\ No newline at end of file
+}
+
+pub fn query_stream_info(
+    deps: Deps, 
+    env: Env,
+    collection_address: Addr
+) -> StdResult<StreamInfoResponse> {
+    let mut stream = util::load_stream(deps.storage, collection_address)?;
+    util::accrue_stream(&mut stream, env.block.time.seconds());
+
+    Ok(StreamInfoResponse {
+        denom: stream.denom,
+        reward_per_second: stream.reward_per_second,
+        total_shares: stream.total_shares,
+        remaining: stream.remaining,
+        total_streamed: stream.total_streamed,
+        server_time: env.block.time.seconds(),
+    })
+}
+
+pub fn query_tx_fee_credit(
+    deps: Deps, 
+    address: Addr
+) -> StdResult<TxFeeCreditResponse> {
+    let credit = FEE_CREDIT_MAP.may_load(deps.storage, address)?.unwrap_or_default();
+    Ok(TxFeeCreditResponse { credit })
+}
+
+pub fn query_pending_owner(
+    deps: Deps, 
+) -> StdResult<PendingOwnerResponse> {
+    let pending = PENDING_OWNER.may_load(deps.storage)?;
+    Ok(PendingOwnerResponse {
+        new_owner: pending.as_ref().map(|pending| pending.new_owner.clone()),
+        expires: pending.map(|pending| pending.expires),
+    })
+}
+
+pub fn query_pending_collection_owner(
+    deps: Deps, 
+    collection_address: Addr
+) -> StdResult<PendingOwnerResponse> {
+    let pending = PENDING_COLLECTION_OWNER_MAP.may_load(deps.storage, collection_address)?;
+    Ok(PendingOwnerResponse {
+        new_owner: pending.as_ref().map(|pending| pending.new_owner.clone()),
+        expires: pending.map(|pending| pending.expires),
+    })
+}
+
+pub fn query_roles(
+    deps: Deps, 
+    address: Addr
+) -> StdResult<RolesResponse> {
+    let roles = Role::ALL
+        .iter()
+        .filter(|role| ROLE_MAP.has(deps.storage, (role.as_str().to_string(), address.clone())))
+        .copied()
+        .collect();
+    Ok(RolesResponse { roles })
+}
+
+pub fn query_role_members(
+    deps: Deps, 
+    role: Role,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<RoleMembersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let addresses = util::get_role_members(deps.storage, role, start_after, limit)?;
+    let next_start_after = if addresses.len() == limit { addresses.last().cloned() } else { None };
+
+    Ok(RoleMembersResponse {
+        addresses,
+        next_start_after
+    })
+}
+
+pub fn query_collection_managers(
+    deps: Deps, 
+    collection_address: Addr,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<RoleMembersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let addresses = util::get_collection_managers(deps.storage, collection_address, start_after, limit)?;
+    let next_start_after = if addresses.len() == limit { addresses.last().cloned() } else { None };
+
+    Ok(RoleMembersResponse {
+        addresses,
+        next_start_after
+    })
+}
+
+pub fn query_pause_info(
+    deps: Deps, 
+    collection_address: Option<Addr>,
+) -> StdResult<PauseInfoResponse> {
+    let global = GLOBAL_PAUSE.may_load(deps.storage)?.unwrap_or_default();
+    let collection = match collection_address {
+        Some(collection_address) => Some(COLLECTION_PAUSE_MAP.may_load(deps.storage, collection_address)?.unwrap_or_default()),
+        None => None,
+    };
+    let effective = match &collection {
+        Some(collection) => global.merge(collection),
+        None => global.clone(),
+    };
+
+    Ok(PauseInfoResponse {
+        global,
+        collection,
+        effective,
+    })
+}
+
+pub fn query_rarity_weights(
+    deps: Deps, 
+    collection_address: Addr,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<RarityWeightsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let default_weight = RARITY_DEFAULT_MAP.may_load(deps.storage, collection_address.clone())?.unwrap_or(1);
+    let weights = util::get_rarity_weights(deps.storage, collection_address, start_after, limit)?;
+    let next_start_after = if weights.len() == limit { weights.last().map(|weight| weight.token_id.clone()) } else { None };
+
+    Ok(RarityWeightsResponse {
+        default_weight,
+        weights,
+        next_start_after
+    })
+}
+
+pub fn query_rarity_ranges(
+    deps: Deps, 
+    collection_address: Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<RarityRangesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let ranges = util::get_rarity_ranges(deps.storage, collection_address, start_after, limit)?;
+    let next_start_after = if ranges.len() == limit { ranges.last().map(|range| range.start) } else { None };
+
+    Ok(RarityRangesResponse {
+        ranges,
+        next_start_after
+    })
+}
+
+pub fn query_token_weight(
+    deps: Deps, 
+    collection_address: Addr,
+    token_id: String,
+) -> StdResult<TokenWeightResponse> {
+    let weight = util::token_weight(deps.storage, collection_address, token_id)?;
+    Ok(TokenWeightResponse { weight })
+}
+
+pub fn query_merkle_airdrop_list(
+    deps: Deps, 
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<MerkleAirdropListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let airdrops = MERKLE_AIRDROP_MAP
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, airdrop)| airdrop))
+        .collect::<StdResult<Vec<MerkleAirdrop>>>()?;
+    let next_start_after = if airdrops.len() == limit { airdrops.last().map(|airdrop| airdrop.id) } else { None };
+
+    Ok(MerkleAirdropListResponse {
+        airdrops,
+        next_start_after
+    })
+}
+
+pub fn query_merkle_claimed(
+    deps: Deps, 
+    airdrop_id: u64,
+    address: Addr,
+) -> StdResult<MerkleClaimedResponse> {
+    let claimed = MERKLE_CLAIMED_MAP.has(deps.storage, (airdrop_id, address));
+    Ok(MerkleClaimedResponse { claimed })
+}
@@ -1,4 +1,5 @@
 use cosmwasm_std::{StdError, Uint128};
+use cw_utils::PaymentError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,6 +7,9 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -33,6 +37,9 @@ pub enum ContractError {
     #[error("No Staked Nfts")]
     NoStakedNft {},
 
+    #[error("Nft already staked")]
+    AlreadyStaked {},
+
     #[error("No Collections")]
     NoCollections {},
 
@@ -64,4 +71,78 @@ pub enum ContractError {
 
     #[error("Invalid collection")]
     InvalidCollection {},
+
+    #[error("No pending owner transfer")]
+    NoPendingOwner {},
+
+    #[error("Owner transfer expired")]
+    OwnerTransferExpired {},
+
+    #[error("No stake time accrued since airdrop start")]
+    NoStakeTime {},
+
+    #[error("Invalid rarity weight")]
+    InvalidWeight {},
+
+    #[error("Overlapping rarity range")]
+    OverlappingRange {},
+
+    #[error("Invalid merkle root")]
+    InvalidMerkleRoot {},
+
+    #[error("Invalid merkle proof")]
+    InvalidMerkleProof {},
+
+    #[error("Merkle airdrop already claimed")]
+    MerkleClaimed {},
+
+    #[error("Merkle airdrop expired")]
+    MerkleExpired {},
+
+    #[error("Merkle airdrop not expired")]
+    MerkleNotExpired {},
+
+    #[error("Invalid vesting")]
+    InvalidVesting {},
+
+    #[error("Invalid lock tier")]
+    InvalidTier {},
+
+    #[error("No unbonded nfts to withdraw")]
+    NoUnbondedNft {},
+
+    #[error("Invalid fee curve")]
+    InvalidFeeCurve {},
+
+    #[error("Invalid fee percent")]
+    InvalidFeePercent {},
+
+    #[error("Insufficient funds")]
+    InsufficientFunds {},
+
+    #[error("Claim operator approval expired")]
+    ClaimOperatorExpired {},
+
+    #[error("Paused")]
+    Paused {},
+
+    #[error("Invalid denom")]
+    InvalidDenom {},
+
+    #[error("Invalid stream")]
+    InvalidStream {},
+
+    #[error("Semver parsing error: {0}")]
+    SemVer(String),
+
+    #[error("Cannot migrate from contract {previous_contract}")]
+    InvalidMigrationContract {
+        previous_contract: String
+    },
+
+    #[error("Cannot migrate from version {previous_version} to {new_version}")]
+    InvalidMigrationVersion {
+        previous_version: String,
+        new_version: String
+    },
 }
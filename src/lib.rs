@@ -1,7 +1,11 @@
 pub mod contract;
 mod error;
+pub mod migrations;
 pub mod msg;
 pub mod state;
 pub mod util;
 
+#[cfg(test)]
+mod testing;
+
 pub use crate::error::ContractError;
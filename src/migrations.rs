@@ -0,0 +1,231 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Order, StdResult, Storage, Uint128};
+use cw20::Denom;
+use semver::Version;
+
+use crate::error::ContractError;
+use crate::state::{AirdropInfo, EarnInfo};
+
+/// Parses a stored contract version. Deployments before 1.1.0 recorded a
+/// two component version ("1.0"), so missing components are padded with zero.
+pub fn parse_version(version: &str) -> Result<Version, ContractError> {
+    let mut parts: Vec<&str> = version.split('.').collect();
+    while parts.len() < 3 {
+        parts.push("0");
+    }
+    Version::parse(&parts.join(".")).map_err(|err| ContractError::SemVer(err.to_string()))
+}
+
+/// 1.0 tracked rewards by cw20 address, an empty address meaning
+/// `Config.native_token`.
+#[cw_serde]
+pub struct LegacyAirdropInfo {
+    pub cw20_address: Addr,
+    pub total_airdrop: Uint128,
+    pub airdropable: Uint128,
+}
+
+#[cw_serde]
+pub struct LegacyEarnInfo {
+    pub cw20_address: Addr,
+    pub total_earned: Uint128,
+    pub claimable: Uint128,
+}
+
+fn legacy_denom(native_token: &str, cw20_address: Addr) -> Denom {
+    if cw20_address.as_str().is_empty() {
+        Denom::Native(native_token.to_string())
+    } else {
+        Denom::Cw20(cw20_address)
+    }
+}
+
+fn convert_airdrop_infos(
+    native_token: &str,
+    total_airdrop: Uint128,
+    airdropable: Uint128,
+    legacy_infos: Vec<LegacyAirdropInfo>,
+) -> Vec<AirdropInfo> {
+    let mut airdrop_infos = vec![];
+    if !total_airdrop.is_zero() || !airdropable.is_zero() {
+        airdrop_infos.push(AirdropInfo {
+            denom: Denom::Native(native_token.to_string()),
+            total_airdrop,
+            airdropable,
+        });
+    }
+    for legacy in legacy_infos {
+        airdrop_infos.push(AirdropInfo {
+            denom: legacy_denom(native_token, legacy.cw20_address),
+            total_airdrop: legacy.total_airdrop,
+            airdropable: legacy.airdropable,
+        });
+    }
+    airdrop_infos
+}
+
+fn convert_earn_infos(
+    native_token: &str,
+    total_earned: Uint128,
+    claimable: Uint128,
+    legacy_infos: Vec<LegacyEarnInfo>,
+) -> Vec<EarnInfo> {
+    let mut earn_infos = vec![];
+    if !total_earned.is_zero() || !claimable.is_zero() {
+        earn_infos.push(EarnInfo {
+            denom: Denom::Native(native_token.to_string()),
+            total_earned,
+            claimable,
+        });
+    }
+    for legacy in legacy_infos {
+        earn_infos.push(EarnInfo {
+            denom: legacy_denom(native_token, legacy.cw20_address),
+            total_earned: legacy.total_earned,
+            claimable: legacy.claimable,
+        });
+    }
+    earn_infos
+}
+
+/// 1.0 kept every staker inside the `Collection` entry. They move into
+/// `user_map` and `staked_nft_map`, every staked nft counting as one stream
+/// share, and the fund ledger is rebuilt from what is now owed.
+pub mod v1_0 {
+    use super::*;
+
+    use cosmwasm_std::Decimal256;
+    use cw_storage_plus::Map;
+
+    use crate::state::{
+        AirdropMode, Collection, FeeCurve, FundInfo, NftInfo, UserInfo,
+        ALLOCATED_FUNDS, COLLECTION_FUNDS_MAP, COLLECTION_MAP, COLLECTION_MAP_PREFIX, CONFIG, STREAM_MAP,
+        staked_nft_map, user_map,
+    };
+    use crate::util;
+
+    #[cw_serde]
+    pub struct LegacyCollection {
+        pub address: Addr,
+        pub owner: Addr,
+        pub total_airdrop: Uint128,
+        pub airdropable: Uint128,
+        pub airdrop_infos: Vec<LegacyAirdropInfo>,
+        pub duration: u64,
+        pub started_at: u64,
+        pub fee_address: Addr,
+        pub cw20_address: Addr,
+        pub is_started: bool,
+        pub is_enabled: bool,
+        pub is_show: bool,
+        pub unstaking_fee: Uint128,
+        pub unstaking_fee_percent: u64,
+        pub users: Vec<LegacyUserInfo>,
+    }
+
+    #[cw_serde]
+    pub struct LegacyNftInfo {
+        pub nft_id: String,
+        pub lock_time: u64,
+        pub airdrop: bool,
+        pub collection_address: Addr,
+    }
+
+    #[cw_serde]
+    pub struct LegacyUserInfo {
+        pub address: Addr,
+        pub total_earnd: Uint128,
+        pub claimable: Uint128,
+        pub earn_infos: Vec<LegacyEarnInfo>,
+        pub staked_nfts: Vec<LegacyNftInfo>,
+    }
+
+    const LEGACY_COLLECTION_MAP: Map<Addr, LegacyCollection> = Map::new(COLLECTION_MAP_PREFIX);
+
+    pub fn migrate(storage: &mut dyn Storage) -> StdResult<()> {
+        let cfg = CONFIG.load(storage)?;
+        let legacy_collections = LEGACY_COLLECTION_MAP
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<(Addr, LegacyCollection)>>>()?;
+
+        let mut allocated: Vec<FundInfo> = vec![];
+        for (collection_address, legacy) in legacy_collections {
+            let collection = Collection {
+                address: legacy.address,
+                owner: legacy.owner,
+                airdrop_infos: convert_airdrop_infos(&cfg.native_token, legacy.total_airdrop, legacy.airdropable, legacy.airdrop_infos),
+                native_denoms: vec![cfg.native_token.clone()],
+                duration: legacy.duration,
+                started_at: legacy.started_at,
+                fee_address: legacy.fee_address,
+                cw20_address: legacy.cw20_address,
+                is_started: legacy.is_started,
+                is_enabled: legacy.is_enabled,
+                is_show: legacy.is_show,
+                unstaking_fee: legacy.unstaking_fee,
+                unstaking_fee_percent: legacy.unstaking_fee_percent,
+                cw20_denoms: vec![],
+                airdrop_mode: AirdropMode::default(),
+                vesting: None,
+                lock_tiers: vec![],
+                unbonding_duration: 0,
+                fee_curve: FeeCurve::default(),
+                fee_denom: None,
+                tx_fee: Uint128::zero(),
+                tx_fee_percent: 0,
+            };
+            COLLECTION_MAP.save(storage, collection_address.clone(), &collection)?;
+
+            let mut stream = util::load_stream(storage, collection_address.clone())?;
+            for legacy_user in legacy.users {
+                let mut shares = Uint128::zero();
+                for legacy_nft in legacy_user.staked_nfts {
+                    if legacy_nft.collection_address != collection_address {
+                        continue;
+                    }
+                    let nftinfo = NftInfo {
+                        nft_id: legacy_nft.nft_id.clone(),
+                        owner: legacy_user.address.clone(),
+                        lock_time: legacy_nft.lock_time,
+                        airdrop: legacy_nft.airdrop,
+                        collection_address: collection_address.clone(),
+                        staked_at: 0,
+                        weight: 0,
+                        auto_relock: None,
+                        tier: None,
+                        multiplier: 0,
+                        lock_tier: None,
+                    };
+                    shares += nftinfo.share();
+                    staked_nft_map().save(storage, (collection_address.clone(), legacy_nft.nft_id), &nftinfo)?;
+                }
+
+                let userinfo = UserInfo {
+                    address: legacy_user.address.clone(),
+                    earn_infos: convert_earn_infos(&cfg.native_token, legacy_user.total_earnd, legacy_user.claimable, legacy_user.earn_infos),
+                    shares,
+                    reward_per_share: Decimal256::zero(),
+                };
+                user_map().save(storage, (collection_address.clone(), legacy_user.address), &userinfo)?;
+                stream.total_shares += shares;
+            }
+            STREAM_MAP.save(storage, collection_address.clone(), &stream)?;
+
+            let funds = util::collection_liabilities(storage, &collection)?;
+            for fund in funds.iter() {
+                util::add_fund_info(&mut allocated, fund.denom.clone(), fund.amount);
+            }
+            COLLECTION_FUNDS_MAP.save(storage, collection_address, &funds)?;
+        }
+        ALLOCATED_FUNDS.save(storage, &allocated)
+    }
+}
+
+/// Runs the routines needed to bring storage written by `previous` up to the
+/// current layout.
+pub fn migrate_from(storage: &mut dyn Storage, previous: &Version) -> StdResult<()> {
+    if *previous < Version::new(1, 1, 0) {
+        v1_0::migrate(storage)?;
+    }
+    Ok(())
+}
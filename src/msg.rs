@@ -1,7 +1,22 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
 use cosmwasm_std::{Uint128, Addr};
+use cw20::{Cw20ReceiveMsg, Denom};
+use cw721::Cw721ReceiveMsg;
+use cw_utils::Expiration;
 
 use crate::state::AirdropInfo;
+use crate::state::Role;
+use crate::state::PauseInfo;
+use crate::state::AirdropMode;
+use crate::state::RarityRange;
+use crate::state::MerkleAirdrop;
+use crate::state::Vesting;
+use crate::state::VestingInfo;
+use crate::state::LockTier;
+use crate::state::UnbondingNft;
+use crate::state::FeeCurve;
+use crate::state::RevenueInfo;
+use crate::state::FundInfo;
 use crate::state::EarnInfo;
 
 #[cw_serde]
@@ -12,10 +27,43 @@ pub struct InstantiateMsg {
     pub tx_fee: Uint128,
 }
 
+#[cw_serde]
+pub struct MigrateMsg {}
+
 #[cw_serde]
 pub enum ExecuteMsg {
-    UpdateOwner {
+    ProposeOwner {
         owner: Addr,
+        expires: Option<Expiration>,
+    },
+    AcceptOwner {},
+    CancelOwner {},
+    GrantRole {
+        role: Role,
+        address: Addr,
+    },
+    RevokeRole {
+        role: Role,
+        address: Addr,
+    },
+    UpdatePause {
+        collection_address: Option<Addr>,
+        stake: bool,
+        claim: bool,
+        airdrop: bool,
+        withdraw: bool,
+    },
+    UpdateEmergencyUnstake {
+        collection_address: Option<Addr>,
+        enabled: bool,
+    },
+    GrantCollectionManager {
+        collection_address: Addr,
+        address: Addr,
+    },
+    RevokeCollectionManager {
+        collection_address: Addr,
+        address: Addr,
     },
     UpdateFeeAddress {
         fee_address: Addr,
@@ -29,23 +77,85 @@ pub enum ExecuteMsg {
         new_unstaking_fee: Uint128,
         new_unstaking_fee_percent: u64,
         cw20_address: Addr,
+        airdrop_mode: Option<AirdropMode>,
     },
     UpdateCollectionFee {
         collection_address: Addr,
         new_unstaking_fee: Uint128,
         new_unstaking_fee_percent: u64,
     },
+    UpdateFeeCurve {
+        collection_address: Addr,
+        fee_curve: FeeCurve,
+    },
+    /// `tx_fee` is in `fee_denom` units and ignored when `fee_denom` is `None`.
+    UpdateFeeDenom {
+        collection_address: Addr,
+        fee_denom: Option<Denom>,
+        tx_fee: Uint128,
+    },
+    UpdateTxFeeSplit {
+        collection_address: Addr,
+        tx_fee_percent: u64,
+    },
     UpdateCollectionState {
         collection_address: Addr,
         is_show: bool,
     },
+    UpdateCollectionDenoms {
+        collection_address: Addr,
+        native_denoms: Vec<String>,
+    },
+    UpdateCollectionCw20Denoms {
+        collection_address: Addr,
+        cw20_denoms: Vec<Addr>,
+    },
+    ProposeCollectionOwner {
+        collection_address: Addr,
+        owner: Addr,
+        expires: Option<Expiration>,
+    },
+    AcceptCollectionOwner {
+        collection_address: Addr,
+    },
+    CancelCollectionOwner {
+        collection_address: Addr,
+    },
+    UpdateLockTiers {
+        collection_address: Addr,
+        lock_tiers: Vec<LockTier>,
+    },
+    UpdateUnbondingDuration {
+        collection_address: Addr,
+        unbonding_duration: u64,
+    },
+    UpdateCollectionVesting {
+        collection_address: Addr,
+        vesting: Option<Vesting>,
+    },
     UpdateCollection {
         collection_address: Addr,
         new_cw20_address: Addr,
-        new_owner: Addr,
         new_duration: u64,
         new_fee_address: Addr,
-        is_enabled: bool
+        is_enabled: bool,
+        airdrop_mode: Option<AirdropMode>,
+    },
+    UpdateRarityWeights {
+        collection_address: Addr,
+        default_weight: Option<u64>,
+        weights: Vec<TokenWeight>,
+        ranges: Vec<RarityRange>,
+    },
+    RemoveRarityWeights {
+        collection_address: Addr,
+        token_ids: Vec<String>,
+        range_starts: Vec<u64>,
+    },
+    SyncRarityWeights {
+        collection_address: Addr,
+        start_after: Option<String>,
+        limit: Option<u32>,
     },
     Charge {
         collection_address: Addr,
@@ -55,25 +165,63 @@ pub enum ExecuteMsg {
         charge_amount: Uint128
     },
     Withdraw {
+        denom: Denom,
         amount: Uint128
     },
     WithdrawAirdrop {
         collection_address: Addr,
-        cw20_address: Addr,
+        denom: Denom,
         amount: Uint128
     },
     Airdrop { 
         collection_address: Addr,
-        cw20_address: Addr,
+        denom: Denom,
         airdrop_amount: Uint128 
     },
     AirdropRestart {
         collection_address: Addr,
     },
-    // ReceiveNft (Cw721ReceiveMsg),
+    /// `merkle_root` is the hex root over `util::merkle_leaf(address, amount)`
+    /// leaves hashed together with `util::merkle_node`.
+    CreateMerkleAirdrop {
+        collection_address: Addr,
+        denom: Denom,
+        merkle_root: String,
+        total_amount: Uint128,
+        expiration: Expiration,
+    },
+    ClaimMerkleAirdrop {
+        airdrop_id: u64,
+        amount: Uint128,
+        proof: Vec<String>,
+    },
+    ReclaimMerkleAirdrop {
+        airdrop_id: u64,
+    },
+    ChargeStream {
+        collection_address: Addr,
+        denom: Denom,
+        amount: Uint128
+    },
+    WithdrawStream {
+        collection_address: Addr,
+        amount: Uint128
+    },
+    UpdateStreamRate {
+        collection_address: Addr,
+        reward_per_second: Uint128
+    },
+    ReceiveNft (Cw721ReceiveMsg),
+    Receive (Cw20ReceiveMsg),
+    DepositTxFee {},
+    /// Refunds unused `DepositTxFee` credit, all of it when `amount` is `None`.
+    WithdrawTxFeeCredit {
+        amount: Option<Uint128>,
+    },
     Restake { 
         collection_address: Addr,
         token_id: Vec<String>,
+        tier: Option<u32>,
     },
     Unstake { 
         collection_address: Addr,
@@ -81,11 +229,47 @@ pub enum ExecuteMsg {
     },
     Staking {
         collection_address: Addr,
-        token_id: Vec<String>
+        token_id: Vec<String>,
+        tier: Option<u32>,
     },
+    /// `owner` claims for another user through a claim operator approval.
+    /// Operators can't pick `recipient`, the user's withdraw address applies.
     Claim { 
         collection_address: Addr,
-        cw20_address: Addr,
+        denom: Denom,
+        owner: Option<Addr>,
+        recipient: Option<Addr>,
+    },
+    ApproveClaimOperator {
+        operator: Addr,
+        expires: Option<Expiration>,
+    },
+    RevokeClaimOperator {
+        operator: Addr,
+    },
+    SetWithdrawAddress {
+        address: Option<Addr>,
+    },
+    SetAutoRelock {
+        collection_address: Addr,
+        token_ids: Option<Vec<String>>,
+        enabled: bool,
+    },
+    /// Claims from every collection the sender is in, or only the listed ones.
+    ClaimAll {
+        collection_addresses: Option<Vec<Addr>>,
+        owner: Option<Addr>,
+        recipient: Option<Addr>,
+    },
+    /// Takes `owner` and `recipient` like `Claim`.
+    WithdrawVested {
+        collection_address: Addr,
+        denom: Option<Denom>,
+        owner: Option<Addr>,
+        recipient: Option<Addr>,
+    },
+    WithdrawUnbonded {
+        collection_address: Addr,
     },
     TransferNft {
         collection_address: Addr,
@@ -106,17 +290,26 @@ pub enum QueryMsg {
         collection_address: Addr,
     },
 
+    /// Pages can hold fewer than `limit` addresses, keep going until
+    /// `next_start_after` is `None`.
     #[returns(UserListResponse)]
     GetUserList {
         collection_address: Addr,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
     },
 
     #[returns(CollectionListResponse)]
     GetCollectionList {
+        start_after: Option<Addr>,
+        limit: Option<u32>,
     },
 
+    /// Pages can hold fewer than `limit` collections, as for `GetUserList`.
     #[returns(CollectionListResponse)]
     GetShownCollectionList {
+        start_after: Option<Addr>,
+        limit: Option<u32>,
     },
 
     #[returns(TotalLockedResponse)]
@@ -128,6 +321,14 @@ pub enum QueryMsg {
     GetStakedNfts {
         address: Addr,
         collection_address: Addr,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    #[returns(AutoRelockResponse)]
+    GetAutoRelock {
+        address: Addr,
+        collection_address: Addr,
     },
 
     #[returns(EarnInfosResponse)]
@@ -136,11 +337,141 @@ pub enum QueryMsg {
         collection_address: Addr
     },
 
+    #[returns(VestingInfosResponse)]
+    GetVestingInfos {
+        address: Addr,
+        collection_address: Addr,
+    },
+
+    /// `denoms` compares the ledger's allocated totals with live balances.
+    /// `collections` recomputes one page of collections (one by default, at
+    /// most five) from raw state and compares it with their ledger entries.
+    #[returns(AuditResponse)]
+    Audit {
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+
+    /// Ledger for one collection, or everything allocated when `None`.
+    #[returns(FundsResponse)]
+    GetFunds {
+        collection_address: Option<Addr>,
+    },
+
+    /// Totals for one collection, or summed over all collections.
+    #[returns(RevenueResponse)]
+    GetRevenue {
+        collection_address: Option<Addr>,
+    },
+
+    #[returns(UnstakeFeeResponse)]
+    GetUnstakeFee {
+        collection_address: Addr,
+        token_ids: Vec<String>,
+    },
+
+    #[returns(UnbondingNftsResponse)]
+    GetUnbondingNfts {
+        address: Addr,
+        collection_address: Addr,
+    },
+
     #[returns(AirdropInfosResponse)]
     GetAirdropInfos {
         address: Addr,
     },
 
+    #[returns(StreamInfoResponse)]
+    GetStreamInfo {
+        collection_address: Addr,
+    },
+
+    #[returns(TxFeeCreditResponse)]
+    GetTxFeeCredit {
+        address: Addr,
+    },
+
+    #[returns(PendingOwnerResponse)]
+    GetPendingOwner {},
+
+    #[returns(PendingOwnerResponse)]
+    GetPendingCollectionOwner {
+        collection_address: Addr,
+    },
+
+    #[returns(RolesResponse)]
+    GetRoles {
+        address: Addr,
+    },
+
+    #[returns(RoleMembersResponse)]
+    GetRoleMembers {
+        role: Role,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+
+    #[returns(RoleMembersResponse)]
+    GetCollectionManagers {
+        collection_address: Addr,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+
+    #[returns(ClaimOperatorsResponse)]
+    GetClaimOperators {
+        owner: Addr,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+
+    #[returns(WithdrawAddressResponse)]
+    GetWithdrawAddress {
+        owner: Addr,
+    },
+
+    #[returns(RarityWeightsResponse)]
+    GetRarityWeights {
+        collection_address: Addr,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    #[returns(RarityRangesResponse)]
+    GetRarityRanges {
+        collection_address: Addr,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    #[returns(TokenWeightResponse)]
+    GetTokenWeight {
+        collection_address: Addr,
+        token_id: String,
+    },
+
+    #[returns(MerkleAirdrop)]
+    GetMerkleAirdrop {
+        airdrop_id: u64,
+    },
+
+    #[returns(MerkleAirdropListResponse)]
+    GetMerkleAirdropList {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    #[returns(MerkleClaimedResponse)]
+    GetMerkleClaimed {
+        airdrop_id: u64,
+        address: Addr,
+    },
+
+    #[returns(PauseInfoResponse)]
+    GetPauseInfo {
+        collection_address: Option<Addr>,
+    },
+
 }
 
 #[cw_serde]
@@ -154,8 +485,8 @@ pub struct ConfigResponse {
 pub struct CollectionResponse {
     pub address: Addr,
     pub owner: Addr,
-    pub total_airdrop: Uint128,
-    pub airdropable: Uint128,
+    pub airdrop_infos: Vec<AirdropInfo>,
+    pub native_denoms: Vec<String>,
     pub duration: u64,
     pub started_at: u64,
     pub fee_address: Addr,
@@ -165,55 +496,263 @@ pub struct CollectionResponse {
     pub is_enabled: bool,
     pub unstaking_fee: Uint128,
     pub unstaking_fee_percent: u64,
+    pub cw20_denoms: Vec<Addr>,
+    pub airdrop_mode: AirdropMode,
+    pub vesting: Option<Vesting>,
+    pub lock_tiers: Vec<LockTier>,
+    pub unbonding_duration: u64,
+    pub fee_curve: FeeCurve,
+    pub fee_denom: Denom,
+    pub tx_fee: Uint128,
+    pub tx_fee_percent: u64,
     pub server_time: u64,
 }
 
 #[cw_serde]
 pub struct CollectionListResponse {
     pub collections: Vec<Addr>,
+    pub next_start_after: Option<Addr>,
 }
 
 #[cw_serde]
 pub struct UserListResponse {
     pub addresses: Vec<Addr>,
+    pub next_start_after: Option<Addr>,
 }
 
 #[cw_serde]
 pub struct TotalLockedResponse {
     pub count: Uint128,
+    pub shares: Uint128,
 }
 
 #[cw_serde]
 pub struct StakedNftResponse {
     pub nft_id: String,
     pub airdrop: bool,
-    pub lock_time: u64
+    pub lock_time: u64,
+    pub weight: u64,
+    pub auto_relock: bool,
+    pub tier: Option<u32>,
+    pub multiplier: u64,
 }
 
 #[cw_serde]
 pub struct StakedNftsResponse {
     pub nft_maps: Vec<StakedNftResponse>,
+    pub next_start_after: Option<String>,
+}
+
+#[cw_serde]
+pub struct AutoRelockResponse {
+    pub enabled: bool,
 }
 
 #[cw_serde]
 pub struct EarnInfosResponse {
-    pub total_earned: Uint128,
-    pub claimable: Uint128,
     pub earn_infos: Vec<EarnInfo>
 }
 
+#[cw_serde]
+pub struct VestingBalance {
+    pub denom: Denom,
+    pub locked: Uint128,
+    pub unlocked: Uint128,
+}
+
+#[cw_serde]
+pub struct VestingInfosResponse {
+    pub vestings: Vec<VestingInfo>,
+    pub balances: Vec<VestingBalance>,
+    pub server_time: u64,
+}
+
+#[cw_serde]
+pub struct DenomAudit {
+    pub denom: Denom,
+    pub liabilities: Uint128,
+    /// `None` when the balance query failed, see `error`.
+    pub balance: Option<Uint128>,
+    pub shortfall: Option<Uint128>,
+    pub surplus: Option<Uint128>,
+    pub error: Option<String>,
+}
+
+#[cw_serde]
+pub struct CollectionAudit {
+    pub collection_address: Addr,
+    pub liabilities: Vec<FundInfo>,
+    pub ledger: Vec<FundInfo>,
+    pub in_sync: bool,
+}
+
+#[cw_serde]
+pub struct AuditResponse {
+    pub denoms: Vec<DenomAudit>,
+    pub collections: Vec<CollectionAudit>,
+    pub next_start_after: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct FundsResponse {
+    pub funds: Vec<FundInfo>,
+}
+
+#[cw_serde]
+pub struct RevenueResponse {
+    pub revenues: Vec<RevenueInfo>,
+}
+
+#[cw_serde]
+pub struct TokenFee {
+    pub token_id: String,
+    pub fee: Uint128,
+}
+
+#[cw_serde]
+pub struct UnstakeFeeResponse {
+    pub fees: Vec<TokenFee>,
+    pub total_fee: Uint128,
+    pub denom: Denom,
+    pub server_time: u64,
+}
+
+#[cw_serde]
+pub struct UnbondingNftsResponse {
+    pub unbonding: Vec<UnbondingNft>,
+    pub server_time: u64,
+}
+
 #[cw_serde]
 pub struct AirdropInfosResponse {
-    pub total_airdrop: Uint128,
-    pub airdropable: Uint128,
     pub airdrop_infos: Vec<AirdropInfo>
 }
 
+#[cw_serde]
+pub struct StreamInfoResponse {
+    pub denom: Denom,
+    pub reward_per_second: Uint128,
+    pub total_shares: Uint128,
+    pub remaining: Uint128,
+    pub total_streamed: Uint128,
+    pub server_time: u64,
+}
+
+#[cw_serde]
+pub struct TxFeeCreditResponse {
+    pub credit: Uint128,
+}
+
+#[cw_serde]
+pub struct PendingOwnerResponse {
+    pub new_owner: Option<Addr>,
+    pub expires: Option<Expiration>,
+}
+
+#[cw_serde]
+pub struct RolesResponse {
+    pub roles: Vec<Role>,
+}
+
+#[cw_serde]
+pub struct RoleMembersResponse {
+    pub addresses: Vec<Addr>,
+    pub next_start_after: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct ClaimOperator {
+    pub operator: Addr,
+    pub expires: Expiration,
+}
+
+#[cw_serde]
+pub struct ClaimOperatorsResponse {
+    pub operators: Vec<ClaimOperator>,
+    pub next_start_after: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct WithdrawAddressResponse {
+    pub address: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct TokenWeight {
+    pub token_id: String,
+    pub weight: u64,
+}
+
+#[cw_serde]
+pub struct RarityWeightsResponse {
+    pub default_weight: u64,
+    pub weights: Vec<TokenWeight>,
+    pub next_start_after: Option<String>,
+}
+
+#[cw_serde]
+pub struct RarityRangesResponse {
+    pub ranges: Vec<RarityRange>,
+    pub next_start_after: Option<u64>,
+}
+
+#[cw_serde]
+pub struct TokenWeightResponse {
+    pub weight: u64,
+}
+
+#[cw_serde]
+pub struct MerkleAirdropListResponse {
+    pub airdrops: Vec<MerkleAirdrop>,
+    pub next_start_after: Option<u64>,
+}
+
+#[cw_serde]
+pub struct MerkleClaimedResponse {
+    pub claimed: bool,
+}
+
+#[cw_serde]
+pub struct PauseInfoResponse {
+    pub global: PauseInfo,
+    pub collection: Option<PauseInfo>,
+    pub effective: PauseInfo,
+}
+
 #[cw_serde]
 pub enum NftReceiveMsg {
     Stake {
         sender: String,
         token_id: String,
         collection_address: Addr,
+        tier: Option<u32>,
+    },
+    StakeBatch {
+        sender: String,
+        token_ids: Vec<String>,
+        collection_address: Addr,
+        tier: Option<u32>,
     }
-}
\ No newline at end of file
+}
+
+#[cw_serde]
+pub enum TokenReceiveMsg {
+    ChargeCollection {
+        collection_address: Addr,
+    },
+    /// Stake/restake/unstake paying the collection's cw20 fee with the sent amount.
+    Staking {
+        collection_address: Addr,
+        token_id: Vec<String>,
+        tier: Option<u32>,
+    },
+    Restake {
+        collection_address: Addr,
+        token_id: Vec<String>,
+        tier: Option<u32>,
+    },
+    Unstake {
+        collection_address: Addr,
+        token_id: Vec<String>,
+    },
+}
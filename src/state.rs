@@ -1,6 +1,8 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Addr, Decimal256, Uint128};
+use cw20::Denom;
+use cw_utils::Expiration;
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 
 #[cw_serde]
 pub struct Config {
@@ -13,18 +15,79 @@ pub struct Config {
 
 #[cw_serde]
 pub struct AirdropInfo {
-    pub cw20_address: Addr,
+    pub denom: Denom,
     pub total_airdrop: Uint128,
     pub airdropable: Uint128,
 }
 
+/// Fees a collection has generated, split by who received them.
+#[cw_serde]
+pub struct RevenueInfo {
+    pub denom: Denom,
+    pub protocol: Uint128,
+    pub collection: Uint128,
+}
+
+#[cw_serde]
+pub struct FundInfo {
+    pub denom: Denom,
+    pub amount: Uint128,
+}
+
+/// How `Airdrop` splits an amount between NFTs still in lock time.
+#[cw_serde]
+#[derive(Copy, Default)]
+pub enum AirdropMode {
+    /// One equal share per NFT.
+    #[default]
+    Even,
+    /// Shares proportional to NFT-seconds staked since `started_at`.
+    TimeWeighted,
+}
+
+/// Claimed rewards unlock linearly over `duration` seconds, with nothing
+/// unlocked before `cliff`. A cliff equal to the duration releases all at once.
+#[cw_serde]
+pub struct Vesting {
+    pub cliff: u64,
+    pub duration: u64,
+}
+
+/// How the early-unstake fee scales with the lock time left. `Flat` charges
+/// the full fee while locked, `Linear` decays it to zero at `lock_time`.
+#[cw_serde]
+#[derive(Default)]
+pub enum FeeCurve {
+    #[default]
+    Flat,
+    Linear,
+    Stepwise { brackets: Vec<FeeBracket> },
+}
+
+/// Charges `percent` of the fee while at least `min_remaining` seconds of
+/// lock are left. The highest matching bracket applies. Brackets are listed
+/// by increasing `min_remaining`.
+#[cw_serde]
+pub struct FeeBracket {
+    pub min_remaining: u64,
+    pub percent: u64,
+}
+
+/// Optional lock length picked at stake time. `multiplier` is a relative
+/// integer like rarity weights, so a 1x/1.5x/2x ladder is written as 2/3/4.
+#[cw_serde]
+pub struct LockTier {
+    pub duration: u64,
+    pub multiplier: u64,
+    pub unstaking_fee: Uint128,
+}
+
 #[cw_serde]
 pub struct Collection {
     pub address: Addr,
     pub owner: Addr,
-    pub total_airdrop: Uint128,
-    pub airdropable: Uint128,
     pub airdrop_infos: Vec<AirdropInfo>,
+    pub native_denoms: Vec<String>,
     pub duration: u64,
     pub started_at: u64,
     pub fee_address: Addr,
@@ -34,20 +97,76 @@ pub struct Collection {
     pub is_show: bool,
     pub unstaking_fee: Uint128,
     pub unstaking_fee_percent: u64,
-    pub users: Vec<UserInfo>
+    /// cw20 contracts accepted by `TokenReceiveMsg::ChargeCollection`.
+    #[serde(default)]
+    pub cw20_denoms: Vec<Addr>,
+    #[serde(default)]
+    pub airdrop_mode: AirdropMode,
+    #[serde(default)]
+    pub vesting: Option<Vesting>,
+    #[serde(default)]
+    pub lock_tiers: Vec<LockTier>,
+    #[serde(default)]
+    pub unbonding_duration: u64,
+    #[serde(default)]
+    pub fee_curve: FeeCurve,
+    /// Denom for `tx_fee` and unstaking fees, `None` for `Config.native_token`.
+    #[serde(default)]
+    pub fee_denom: Option<Denom>,
+    /// `tx_fee` in `fee_denom` units, replacing `Config.tx_fee` while `fee_denom` is set.
+    #[serde(default)]
+    pub tx_fee: Uint128,
+    /// Share of each `tx_fee` sent to `fee_address`, the rest goes to `Config.fee_address`.
+    #[serde(default)]
+    pub tx_fee_percent: u64,
 }
 
 #[cw_serde]
 pub struct  NftInfo {
     pub nft_id: String,
+    pub owner: Addr,
     pub lock_time: u64,
     pub airdrop: bool,
     pub collection_address: Addr,
+    #[serde(default)]
+    pub staked_at: u64,
+    /// Rarity weight snapshotted at stake time, 0 for NFTs staked before weights existed.
+    #[serde(default)]
+    pub weight: u64,
+    /// Rolls `lock_time` over by the collection duration at expiry. `None`
+    /// follows the owner's collection-wide setting.
+    #[serde(default)]
+    pub auto_relock: Option<bool>,
+    /// Index into `Collection.lock_tiers`, `None` for the collection duration.
+    #[serde(default)]
+    pub tier: Option<u32>,
+    /// Tier multiplier snapshotted at stake time, 0 when untiered.
+    #[serde(default)]
+    pub multiplier: u64,
+    /// Tier snapshotted at stake time, so `UpdateLockTiers` doesn't change
+    /// the duration or fee of existing positions.
+    #[serde(default)]
+    pub lock_tier: Option<LockTier>,
+}
+
+impl NftInfo {
+    /// Shares this NFT holds in streams, airdrops and `TotalLocked`.
+    pub fn share(&self) -> Uint128 {
+        Uint128::from(self.weight.max(1)) * Uint128::from(self.multiplier.max(1))
+    }
+}
+
+/// Weight applied to every numeric token id in `start..=end`.
+#[cw_serde]
+pub struct RarityRange {
+    pub start: u64,
+    pub end: u64,
+    pub weight: u64,
 }
 
 #[cw_serde]
 pub struct EarnInfo {
-    pub cw20_address: Addr,
+    pub denom: Denom,
     pub total_earned: Uint128,
     pub claimable: Uint128,
 }
@@ -55,14 +174,277 @@ pub struct EarnInfo {
 #[cw_serde]
 pub struct UserInfo {
     pub address: Addr,
-    pub total_earnd: Uint128,
-    pub claimable: Uint128,
     pub earn_infos: Vec<EarnInfo>,
-    pub staked_nfts: Vec<NftInfo>,
+    #[serde(default)]
+    pub shares: Uint128,
+    #[serde(default)]
+    pub reward_per_share: Decimal256,
+}
+
+/// One claim moved into vesting.
+#[cw_serde]
+pub struct VestingInfo {
+    pub denom: Denom,
+    pub amount: Uint128,
+    pub released: Uint128,
+    pub start: u64,
+    pub cliff: u64,
+    pub duration: u64,
+}
+
+impl VestingInfo {
+    pub fn vested(&self, now: u64) -> Uint128 {
+        let elapsed = now.saturating_sub(self.start);
+        if elapsed >= self.duration {
+            self.amount
+        } else if elapsed < self.cliff {
+            Uint128::zero()
+        } else {
+            self.amount.multiply_ratio(elapsed, self.duration)
+        }
+    }
+}
+
+/// Continuous emission for a collection. `reward_per_share` only grows, and
+/// a user's pending reward is `shares * (reward_per_share - user.reward_per_share)`.
+#[cw_serde]
+pub struct StreamInfo {
+    pub denom: Denom,
+    pub reward_per_second: Uint128,
+    pub reward_per_share: Decimal256,
+    pub total_shares: Uint128,
+    pub remaining: Uint128,
+    pub total_streamed: Uint128,
+    pub last_updated: u64,
+    /// Emitted but not yet settled into users' `claimable`, including the
+    /// dust left by flooring each user's pending reward.
+    #[serde(default)]
+    pub unsettled: Uint128,
+}
+
+/// NFT held by the contract after `Unstake` until `release_at`. It earns
+/// nothing and can't be restaked.
+#[cw_serde]
+pub struct UnbondingNft {
+    pub nft_id: String,
+    pub release_at: u64,
+}
+
+#[cw_serde]
+pub struct ReceiveBatch {
+    pub height: u64,
+    pub token_ids: Vec<String>,
+}
+
+#[cw_serde]
+#[derive(Copy)]
+pub enum Role {
+    Operator,
+    Pauser,
+    Treasurer,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Operator, Role::Pauser, Role::Treasurer];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Operator => "operator",
+            Role::Pauser => "pauser",
+            Role::Treasurer => "treasurer",
+        }
+    }
+}
+
+/// Operations frozen by `UpdatePause`. `emergency_unstake` is only set by the
+/// owner and lets stakers leave without the unstaking fee or lock time.
+#[cw_serde]
+#[derive(Default)]
+pub struct PauseInfo {
+    pub stake: bool,
+    pub claim: bool,
+    pub airdrop: bool,
+    pub withdraw: bool,
+    pub emergency_unstake: bool,
+}
+
+impl PauseInfo {
+    pub fn merge(&self, other: &PauseInfo) -> PauseInfo {
+        PauseInfo {
+            stake: self.stake || other.stake,
+            claim: self.claim || other.claim,
+            airdrop: self.airdrop || other.airdrop,
+            withdraw: self.withdraw || other.withdraw,
+            emergency_unstake: self.emergency_unstake || other.emergency_unstake,
+        }
+    }
+}
+
+/// Off-chain computed airdrop funded from a collection pool. Leaves and
+/// nodes are hashed as in `util::merkle_leaf` and `util::merkle_node`.
+#[cw_serde]
+pub struct MerkleAirdrop {
+    pub id: u64,
+    pub collection_address: Addr,
+    pub denom: Denom,
+    pub merkle_root: String,
+    pub total_amount: Uint128,
+    pub claimed_amount: Uint128,
+    pub expiration: Expiration,
+    pub reclaimed: bool,
+}
+
+#[cw_serde]
+pub struct PendingOwner {
+    pub new_owner: Addr,
+    pub expires: Expiration,
 }
 
 pub const CONFIG_KEY: &str = "config";
 pub const CONFIG: Item<Config> = Item::new(CONFIG_KEY);
 
+/// Contract owner proposed through `ProposeOwner`, waiting for `AcceptOwner`.
+pub const PENDING_OWNER_KEY: &str = "pending_owner";
+pub const PENDING_OWNER: Item<PendingOwner> = Item::new(PENDING_OWNER_KEY);
+
+/// Collection owners proposed through `ProposeCollectionOwner`, keyed by collection.
+pub const PENDING_COLLECTION_OWNER_MAP_PREFIX: &str = "pending_collection_owner_map";
+pub const PENDING_COLLECTION_OWNER_MAP: Map<Addr, PendingOwner> = Map::new(PENDING_COLLECTION_OWNER_MAP_PREFIX);
+
+/// Global role holders, keyed by (role name, address).
+pub const ROLE_MAP_PREFIX: &str = "role_map";
+pub const ROLE_MAP: Map<(String, Addr), bool> = Map::new(ROLE_MAP_PREFIX);
+
+/// Per-collection managers, keyed by (collection, manager).
+pub const COLLECTION_MANAGER_MAP_PREFIX: &str = "collection_manager_map";
+pub const COLLECTION_MANAGER_MAP: Map<(Addr, Addr), bool> = Map::new(COLLECTION_MANAGER_MAP_PREFIX);
+
+pub const GLOBAL_PAUSE_KEY: &str = "global_pause";
+pub const GLOBAL_PAUSE: Item<PauseInfo> = Item::new(GLOBAL_PAUSE_KEY);
+
+pub const COLLECTION_PAUSE_MAP_PREFIX: &str = "collection_pause_map";
+pub const COLLECTION_PAUSE_MAP: Map<Addr, PauseInfo> = Map::new(COLLECTION_PAUSE_MAP_PREFIX);
+
+/// Rarity weight for tokens not covered by a token or range entry, 1 if unset.
+pub const RARITY_DEFAULT_MAP_PREFIX: &str = "rarity_default_map";
+pub const RARITY_DEFAULT_MAP: Map<Addr, u64> = Map::new(RARITY_DEFAULT_MAP_PREFIX);
+
+/// Rarity weights keyed by (collection, token_id).
+pub const RARITY_WEIGHT_MAP_PREFIX: &str = "rarity_weight_map";
+pub const RARITY_WEIGHT_MAP: Map<(Addr, String), u64> = Map::new(RARITY_WEIGHT_MAP_PREFIX);
+
+/// Rarity weight ranges keyed by (collection, range start).
+pub const RARITY_RANGE_MAP_PREFIX: &str = "rarity_range_map";
+pub const RARITY_RANGE_MAP: Map<(Addr, u64), RarityRange> = Map::new(RARITY_RANGE_MAP_PREFIX);
+
+pub const MERKLE_AIRDROP_COUNT_KEY: &str = "merkle_airdrop_count";
+pub const MERKLE_AIRDROP_COUNT: Item<u64> = Item::new(MERKLE_AIRDROP_COUNT_KEY);
+
+pub const MERKLE_AIRDROP_MAP_PREFIX: &str = "merkle_airdrop_map";
+pub const MERKLE_AIRDROP_MAP: Map<u64, MerkleAirdrop> = Map::new(MERKLE_AIRDROP_MAP_PREFIX);
+
+/// Merkle airdrops still holding funds, keyed by (collection, airdrop id).
+pub const OPEN_MERKLE_AIRDROP_MAP_PREFIX: &str = "open_merkle_airdrop_map";
+pub const OPEN_MERKLE_AIRDROP_MAP: Map<(Addr, u64), bool> = Map::new(OPEN_MERKLE_AIRDROP_MAP_PREFIX);
+
+/// Claimed leaves, keyed by (airdrop id, address).
+pub const MERKLE_CLAIMED_MAP_PREFIX: &str = "merkle_claimed_map";
+pub const MERKLE_CLAIMED_MAP: Map<(u64, Addr), bool> = Map::new(MERKLE_CLAIMED_MAP_PREFIX);
+
+/// Vesting claims, keyed by (collection, user).
+pub const VESTING_MAP_PREFIX: &str = "vesting_map";
+pub const VESTING_MAP: Map<(Addr, Addr), Vec<VestingInfo>> = Map::new(VESTING_MAP_PREFIX);
+
+/// Users that auto-relock every position, keyed by (collection, user).
+pub const AUTO_RELOCK_MAP_PREFIX: &str = "auto_relock_map";
+pub const AUTO_RELOCK_MAP: Map<(Addr, Addr), bool> = Map::new(AUTO_RELOCK_MAP_PREFIX);
+
+/// Addresses allowed to claim for a user, keyed by (user, operator).
+pub const CLAIM_OPERATOR_MAP_PREFIX: &str = "claim_operator_map";
+pub const CLAIM_OPERATOR_MAP: Map<(Addr, Addr), Expiration> = Map::new(CLAIM_OPERATOR_MAP_PREFIX);
+
+/// Where a user's claims are paid when no recipient is given.
+pub const WITHDRAW_ADDRESS_MAP_PREFIX: &str = "withdraw_address_map";
+pub const WITHDRAW_ADDRESS_MAP: Map<Addr, Addr> = Map::new(WITHDRAW_ADDRESS_MAP_PREFIX);
+
+/// Unbonding queue, keyed by (collection, user), oldest first.
+pub const UNBONDING_MAP_PREFIX: &str = "unbonding_map";
+pub const UNBONDING_MAP: Map<(Addr, Addr), Vec<UnbondingNft>> = Map::new(UNBONDING_MAP_PREFIX);
+
 pub const COLLECTION_MAP_PREFIX: &str = "collection_map";
-pub const COLLECTION_MAP: Map<Addr, Collection> = Map::new(COLLECTION_MAP_PREFIX);
\ No newline at end of file
+pub const COLLECTION_MAP: Map<Addr, Collection> = Map::new(COLLECTION_MAP_PREFIX);
+
+pub const STREAM_MAP_PREFIX: &str = "stream_map";
+pub const STREAM_MAP: Map<Addr, StreamInfo> = Map::new(STREAM_MAP_PREFIX);
+
+/// Fee revenue routed out of the contract, per collection.
+pub const REVENUE_MAP_PREFIX: &str = "revenue_map";
+pub const REVENUE_MAP: Map<Addr, Vec<RevenueInfo>> = Map::new(REVENUE_MAP_PREFIX);
+
+/// Funds held for a collection: its reward pools, streams and merkle
+/// airdrops plus rewards owed to its stakers.
+pub const COLLECTION_FUNDS_MAP_PREFIX: &str = "collection_funds_map";
+pub const COLLECTION_FUNDS_MAP: Map<Addr, Vec<FundInfo>> = Map::new(COLLECTION_FUNDS_MAP_PREFIX);
+
+/// Everything the contract owes: all collection funds plus prepaid fee
+/// credit. The rest of its balance is protocol revenue.
+pub const ALLOCATED_FUNDS_KEY: &str = "allocated_funds";
+pub const ALLOCATED_FUNDS: Item<Vec<FundInfo>> = Item::new(ALLOCATED_FUNDS_KEY);
+
+/// Prepaid `tx_fee` balance used when staking through cw721 `SendNft`.
+pub const FEE_CREDIT_MAP_PREFIX: &str = "fee_credit_map";
+pub const FEE_CREDIT_MAP: Map<Addr, Uint128> = Map::new(FEE_CREDIT_MAP_PREFIX);
+
+/// Last batch opened through `NftReceiveMsg::StakeBatch`, keyed by (collection, user).
+pub const RECEIVE_BATCH_MAP_PREFIX: &str = "receive_batch_map";
+pub const RECEIVE_BATCH_MAP: Map<(Addr, Addr), ReceiveBatch> = Map::new(RECEIVE_BATCH_MAP_PREFIX);
+
+/// Reward positions, keyed by (collection, user).
+pub const USER_MAP_PREFIX: &str = "user_map";
+
+pub struct UserIndexes<'a> {
+    pub address: MultiIndex<'a, Addr, UserInfo, (Addr, Addr)>,
+}
+
+impl<'a> IndexList<UserInfo> for UserIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<UserInfo>> + '_> {
+        let v: Vec<&dyn Index<UserInfo>> = vec![&self.address];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn user_map<'a>() -> IndexedMap<'a, (Addr, Addr), UserInfo, UserIndexes<'a>> {
+    let indexes = UserIndexes {
+        address: MultiIndex::new(
+            |_pk, user: &UserInfo| user.address.clone(),
+            USER_MAP_PREFIX,
+            "user_map__address",
+        ),
+    };
+    IndexedMap::new(USER_MAP_PREFIX, indexes)
+}
+
+/// Staked nfts, keyed by (collection, token_id).
+pub const STAKED_NFT_MAP_PREFIX: &str = "staked_nft_map";
+
+pub struct NftIndexes<'a> {
+    pub owner: MultiIndex<'a, (Addr, Addr), NftInfo, (Addr, String)>,
+}
+
+impl<'a> IndexList<NftInfo> for NftIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<NftInfo>> + '_> {
+        let v: Vec<&dyn Index<NftInfo>> = vec![&self.owner];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn staked_nft_map<'a>() -> IndexedMap<'a, (Addr, String), NftInfo, NftIndexes<'a>> {
+    let indexes = NftIndexes {
+        owner: MultiIndex::new(
+            |_pk, nft: &NftInfo| (nft.collection_address.clone(), nft.owner.clone()),
+            STAKED_NFT_MAP_PREFIX,
+            "staked_nft_map__owner",
+        ),
+    };
+    IndexedMap::new(STAKED_NFT_MAP_PREFIX, indexes)
+}
@@ -0,0 +1,1336 @@
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
+use cosmwasm_std::{coins, from_binary, to_binary, Addr, BankMsg, Coin, CosmosMsg, Env, Order, OwnedDeps, Response, Uint128, WasmMsg};
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{Cw20ReceiveMsg, Denom};
+use cw721::{Cw721ExecuteMsg, Cw721ReceiveMsg};
+use cw_storage_plus::Map;
+use cw_utils::{Expiration, PaymentError};
+
+use crate::contract::{execute, instantiate, migrate, query};
+use crate::msg::{
+    AuditResponse, CollectionListResponse, CollectionResponse, EarnInfosResponse, ExecuteMsg, FundsResponse, InstantiateMsg,
+    MigrateMsg, NftReceiveMsg, QueryMsg, StakedNftsResponse, TokenReceiveMsg, TokenWeightResponse, UnstakeFeeResponse,
+    UserListResponse,
+};
+use crate::state::{
+    AirdropInfo, AirdropMode, Config, EarnInfo, FeeBracket, FeeCurve, FundInfo, LockTier, RarityRange, Role, Vesting,
+    ALLOCATED_FUNDS, COLLECTION_FUNDS_MAP, COLLECTION_MAP, COLLECTION_MAP_PREFIX, CONFIG, FEE_CREDIT_MAP, STREAM_MAP,
+    UNBONDING_MAP,
+};
+use crate::util;
+use crate::ContractError;
+
+const NATIVE: &str = "inj";
+const COLLECTION: &str = "collection";
+const COLLECTION_OWNER: &str = "collection_owner";
+
+type Deps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+fn collection() -> Addr {
+    Addr::unchecked(COLLECTION)
+}
+
+fn native() -> Denom {
+    Denom::Native(NATIVE.to_string())
+}
+
+/// Instantiates with a tx fee of 1 and registers `COLLECTION`.
+fn setup() -> (Deps, Env) {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    instantiate(deps.as_mut(), env.clone(), mock_info("owner", &[]), InstantiateMsg {
+        owner: Addr::unchecked("owner"),
+        fee_address: Addr::unchecked("fee"),
+        native_token: NATIVE.to_string(),
+        tx_fee: Uint128::new(1),
+    }).unwrap();
+    register_collection(&mut deps, &env, COLLECTION);
+    (deps, env)
+}
+
+/// Registers a started collection with 1000 native tokens charged.
+fn register_collection(deps: &mut Deps, env: &Env, collection_address: &str) {
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::RegisteCollection {
+        collection_address: Addr::unchecked(collection_address),
+        owner: Addr::unchecked(COLLECTION_OWNER),
+        new_unstaking_fee: Uint128::zero(),
+        new_unstaking_fee_percent: 0,
+        cw20_address: Addr::unchecked(""),
+        airdrop_mode: None,
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::AirdropRestart {
+        collection_address: Addr::unchecked(collection_address),
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &coins(1000, NATIVE)), ExecuteMsg::Charge {
+        collection_address: Addr::unchecked(collection_address),
+    }).unwrap();
+}
+
+fn update_duration(deps: &mut Deps, env: &Env, duration: u64) {
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::UpdateCollection {
+        collection_address: collection(),
+        new_cw20_address: Addr::unchecked(""),
+        new_duration: duration,
+        new_fee_address: Addr::unchecked(COLLECTION_OWNER),
+        is_enabled: true,
+        airdrop_mode: None,
+    }).unwrap();
+}
+
+fn stake(deps: &mut Deps, env: &Env, sender: &str, token_ids: &[&str]) -> Result<Response, ContractError> {
+    execute(deps.as_mut(), env.clone(), mock_info(sender, &coins(1, NATIVE)), ExecuteMsg::Staking {
+        collection_address: collection(),
+        token_id: token_ids.iter().map(|id| id.to_string()).collect(),
+        tier: None,
+    })
+}
+
+fn staked_nfts(deps: &Deps, env: &Env, address: &str) -> StakedNftsResponse {
+    from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetStakedNfts {
+        address: Addr::unchecked(address),
+        collection_address: collection(),
+        start_after: None,
+        limit: None,
+    }).unwrap()).unwrap()
+}
+
+#[test]
+fn staking_rejects_staked_nfts() {
+    let (mut deps, env) = setup();
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+
+    let err = stake(&mut deps, &env, "mallory", &["1"]).unwrap_err();
+    assert!(matches!(err, ContractError::AlreadyStaked {}));
+    let err = stake(&mut deps, &env, "bob", &["2", "2"]).unwrap_err();
+    assert!(matches!(err, ContractError::AlreadyStaked {}));
+    assert_eq!(staked_nfts(&deps, &env, "alice").nft_maps.len(), 1);
+    assert!(staked_nfts(&deps, &env, "mallory").nft_maps.is_empty());
+}
+
+fn legacy_setup(version: &str) -> Deps {
+    let mut deps = mock_dependencies();
+    set_contract_version(deps.as_mut().storage, "Injstaking by AOI", version).unwrap();
+    CONFIG.save(deps.as_mut().storage, &Config {
+        owner: Addr::unchecked("owner"),
+        creator: Addr::unchecked("owner"),
+        fee_address: Addr::unchecked("fee"),
+        native_token: NATIVE.to_string(),
+        tx_fee: Uint128::new(1),
+    }).unwrap();
+    deps
+}
+
+/// Runs `migrate` and checks every collection's ledger against its liabilities
+/// and the allocated totals against the ledger.
+fn migrate_and_check_ledger(deps: &mut Deps) {
+    migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+    assert_eq!(get_contract_version(deps.as_ref().storage).unwrap().version, "1.1.0");
+
+    let mut allocated: Vec<FundInfo> = vec![];
+    for item in COLLECTION_MAP.range(deps.as_ref().storage, None, None, Order::Ascending) {
+        let (collection_address, collection) = item.unwrap();
+        let ledger = COLLECTION_FUNDS_MAP.load(deps.as_ref().storage, collection_address).unwrap();
+        assert_eq!(ledger, util::collection_liabilities(deps.as_ref().storage, &collection).unwrap());
+        for fund in ledger {
+            util::add_fund_info(&mut allocated, fund.denom, fund.amount);
+        }
+    }
+    assert_eq!(ALLOCATED_FUNDS.load(deps.as_ref().storage).unwrap(), allocated);
+}
+
+#[test]
+fn migrate_from_1_0_rebuilds_ledger() {
+    use crate::migrations::v1_0::{LegacyCollection, LegacyNftInfo, LegacyUserInfo};
+    use crate::migrations::{LegacyAirdropInfo, LegacyEarnInfo};
+
+    let mut deps = legacy_setup("1.0");
+    let legacy_nft = |nft_id: &str| LegacyNftInfo {
+        nft_id: nft_id.to_string(),
+        lock_time: 100,
+        airdrop: false,
+        collection_address: collection(),
+    };
+    let legacy: Map<Addr, LegacyCollection> = Map::new(COLLECTION_MAP_PREFIX);
+    legacy.save(deps.as_mut().storage, collection(), &LegacyCollection {
+        address: collection(),
+        owner: Addr::unchecked(COLLECTION_OWNER),
+        total_airdrop: Uint128::new(500),
+        airdropable: Uint128::new(100),
+        airdrop_infos: vec![LegacyAirdropInfo {
+            cw20_address: Addr::unchecked("project"),
+            total_airdrop: Uint128::new(50),
+            airdropable: Uint128::new(20),
+        }],
+        duration: 100,
+        started_at: 0,
+        fee_address: Addr::unchecked(COLLECTION_OWNER),
+        cw20_address: Addr::unchecked(""),
+        is_started: true,
+        is_enabled: true,
+        is_show: true,
+        unstaking_fee: Uint128::zero(),
+        unstaking_fee_percent: 0,
+        users: vec![
+            LegacyUserInfo {
+                address: Addr::unchecked("alice"),
+                total_earnd: Uint128::new(60),
+                claimable: Uint128::new(30),
+                earn_infos: vec![LegacyEarnInfo {
+                    cw20_address: Addr::unchecked("project"),
+                    total_earned: Uint128::new(10),
+                    claimable: Uint128::new(5),
+                }],
+                staked_nfts: vec![legacy_nft("1"), legacy_nft("2")],
+            },
+            LegacyUserInfo {
+                address: Addr::unchecked("bob"),
+                total_earnd: Uint128::new(20),
+                claimable: Uint128::new(12),
+                earn_infos: vec![],
+                staked_nfts: vec![legacy_nft("3")],
+            },
+        ],
+    }).unwrap();
+
+    migrate_and_check_ledger(&mut deps);
+
+    let ledger = COLLECTION_FUNDS_MAP.load(deps.as_ref().storage, collection()).unwrap();
+    assert_eq!(ledger, vec![
+        FundInfo { denom: native(), amount: Uint128::new(100 + 30 + 12) },
+        FundInfo { denom: Denom::Cw20(Addr::unchecked("project")), amount: Uint128::new(20 + 5) },
+    ]);
+    let collection_info = COLLECTION_MAP.load(deps.as_ref().storage, collection()).unwrap();
+    assert_eq!(collection_info.airdrop_infos, vec![
+        AirdropInfo { denom: native(), total_airdrop: Uint128::new(500), airdropable: Uint128::new(100) },
+        AirdropInfo { denom: Denom::Cw20(Addr::unchecked("project")), total_airdrop: Uint128::new(50), airdropable: Uint128::new(20) },
+    ]);
+    assert_eq!(staked_nfts(&deps, &mock_env(), "alice").nft_maps.len(), 2);
+    assert_eq!(staked_nfts(&deps, &mock_env(), "bob").nft_maps.len(), 1);
+    let earn_infos: EarnInfosResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetEarnInfos {
+        address: Addr::unchecked("alice"),
+        collection_address: collection(),
+    }).unwrap()).unwrap();
+    assert_eq!(earn_infos.earn_infos, vec![
+        EarnInfo { denom: native(), total_earned: Uint128::new(60), claimable: Uint128::new(30) },
+        EarnInfo { denom: Denom::Cw20(Addr::unchecked("project")), total_earned: Uint128::new(10), claimable: Uint128::new(5) },
+    ]);
+    let stream = STREAM_MAP.load(deps.as_ref().storage, collection()).unwrap();
+    assert_eq!(stream.total_shares, Uint128::new(3));
+}
+
+#[test]
+fn migrate_rejects_other_contracts_and_downgrades() {
+    let mut deps = legacy_setup("2.0");
+    let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidMigrationVersion { .. }));
+
+    set_contract_version(deps.as_mut().storage, "other", "1.0").unwrap();
+    let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidMigrationContract { .. }));
+}
+
+#[test]
+fn list_queries_page_within_limit() {
+    let (mut deps, env) = setup();
+    for user in ["alice", "bob", "carol", "dave"] {
+        stake(&mut deps, &env, user, &[user]).unwrap();
+    }
+    execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), ExecuteMsg::Unstake {
+        collection_address: collection(),
+        token_id: vec!["carol".to_string()],
+    }).unwrap();
+    let user_list = |start_after: Option<&str>| -> UserListResponse {
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetUserList {
+            collection_address: collection(),
+            start_after: start_after.map(Addr::unchecked),
+            limit: Some(2),
+        }).unwrap()).unwrap()
+    };
+    let res = user_list(None);
+    assert_eq!((res.addresses, res.next_start_after), (vec![Addr::unchecked("alice"), Addr::unchecked("bob")], Some(Addr::unchecked("bob"))));
+    let res = user_list(Some("bob"));
+    assert_eq!((res.addresses, res.next_start_after), (vec![Addr::unchecked("dave")], Some(Addr::unchecked("dave"))));
+    let res = user_list(Some("dave"));
+    assert_eq!((res.addresses, res.next_start_after), (vec![], None));
+
+    register_collection(&mut deps, &env, "collection_b");
+    register_collection(&mut deps, &env, "collection_c");
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::UpdateCollectionState {
+        collection_address: Addr::unchecked("collection_b"),
+        is_show: false,
+    }).unwrap();
+    let collection_list = |shown: bool, start_after: Option<&str>| -> CollectionListResponse {
+        let start_after = start_after.map(Addr::unchecked);
+        let msg = if shown {
+            QueryMsg::GetShownCollectionList { start_after, limit: Some(2) }
+        } else {
+            QueryMsg::GetCollectionList { start_after, limit: Some(2) }
+        };
+        from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+    };
+    let res = collection_list(true, None);
+    assert_eq!((res.collections, res.next_start_after), (vec![collection()], Some(Addr::unchecked("collection_b"))));
+    let res = collection_list(true, Some("collection_b"));
+    assert_eq!((res.collections, res.next_start_after), (vec![Addr::unchecked("collection_c")], None));
+    let res = collection_list(false, Some(COLLECTION));
+    assert_eq!((res.collections, res.next_start_after), (vec![Addr::unchecked("collection_b"), Addr::unchecked("collection_c")], Some(Addr::unchecked("collection_c"))));
+
+    stake(&mut deps, &env, "alice", &["x", "y"]).unwrap();
+    let staked_page = |start_after: Option<&str>| -> StakedNftsResponse {
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetStakedNfts {
+            address: Addr::unchecked("alice"),
+            collection_address: collection(),
+            start_after: start_after.map(String::from),
+            limit: Some(2),
+        }).unwrap()).unwrap()
+    };
+    let res = staked_page(None);
+    assert_eq!(res.nft_maps.iter().map(|nft| nft.nft_id.as_str()).collect::<Vec<_>>(), vec!["alice", "x"]);
+    assert_eq!(res.next_start_after, Some("x".to_string()));
+    let res = staked_page(Some("x"));
+    assert_eq!(res.nft_maps.iter().map(|nft| nft.nft_id.as_str()).collect::<Vec<_>>(), vec!["y"]);
+    assert_eq!(res.next_start_after, None);
+}
+
+
+#[test]
+fn claim_after_full_unstake() {
+    let (mut deps, mut env) = setup();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(1000, NATIVE));
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::ChargeStream {
+        collection_address: collection(),
+        denom: native(),
+        amount: Uint128::new(500),
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::UpdateStreamRate {
+        collection_address: collection(),
+        reward_per_second: Uint128::new(2),
+    }).unwrap();
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+    env.block.time = env.block.time.plus_seconds(10);
+    execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::Unstake {
+        collection_address: collection(),
+        token_id: vec!["1".to_string()],
+    }).unwrap();
+
+    let claim = || ExecuteMsg::Claim { collection_address: collection(), denom: native(), owner: None, recipient: None };
+    let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), claim()).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: "alice".to_string(),
+        amount: coins(20, NATIVE),
+    }));
+    let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), claim()).unwrap_err();
+    assert!(matches!(err, ContractError::NoReward {}));
+}
+
+#[test]
+fn withdraw_tx_fee_credit() {
+    let (mut deps, env) = setup();
+    execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(10, NATIVE)), ExecuteMsg::DepositTxFee {}).unwrap();
+
+    let withdraw = |amount: Option<u128>| ExecuteMsg::WithdrawTxFeeCredit { amount: amount.map(Uint128::new) };
+    let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), withdraw(Some(11))).unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientCw20 {}));
+    let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), withdraw(Some(4))).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: "alice".to_string(),
+        amount: coins(4, NATIVE),
+    }));
+    let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), withdraw(None)).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: "alice".to_string(),
+        amount: coins(6, NATIVE),
+    }));
+
+    let res: FundsResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetFunds {
+        collection_address: None,
+    }).unwrap()).unwrap();
+    assert_eq!(res.funds, vec![FundInfo { denom: native(), amount: Uint128::new(1000) }]);
+    let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), withdraw(None)).unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientCw20 {}));
+}
+
+fn charge_cw20(deps: &mut Deps, env: &Env, cw20_address: &str, amount: u128) -> Result<Response, ContractError> {
+    execute(deps.as_mut(), env.clone(), mock_info(cw20_address, &[]), ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: COLLECTION_OWNER.to_string(),
+        amount: Uint128::new(amount),
+        msg: to_binary(&TokenReceiveMsg::ChargeCollection {
+            collection_address: collection(),
+        }).unwrap(),
+    }))
+}
+
+#[test]
+fn cw20_charge_requires_allowlisted_token() {
+    let (mut deps, env) = setup();
+    let err = charge_cw20(&mut deps, &env, "project", 100).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidCw20Token {}));
+
+    let update_cw20_denoms = ExecuteMsg::UpdateCollectionCw20Denoms {
+        collection_address: collection(),
+        cw20_denoms: vec![Addr::unchecked("project")],
+    };
+    let err = execute(deps.as_mut(), env.clone(), mock_info("mallory", &[]), update_cw20_denoms.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), update_cw20_denoms).unwrap();
+
+    charge_cw20(&mut deps, &env, "project", 100).unwrap();
+    let err = charge_cw20(&mut deps, &env, "fake", 100).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidCw20Token {}));
+    let res: CollectionResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetCollection {
+        collection_address: collection(),
+    }).unwrap()).unwrap();
+    let project = Denom::Cw20(Addr::unchecked("project"));
+    assert!(res.airdrop_infos.iter().any(|info| info.denom == project && info.airdropable == Uint128::new(100)));
+    assert!(!res.airdrop_infos.iter().any(|info| info.denom == Denom::Cw20(Addr::unchecked("fake"))));
+}
+
+fn claim(deps: &mut Deps, env: &Env, sender: &str) -> Result<Response, ContractError> {
+    execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), ExecuteMsg::Claim {
+        collection_address: collection(),
+        denom: native(),
+        owner: None,
+        recipient: None,
+    })
+}
+
+fn paid(res: &Response, address: &str) -> Uint128 {
+    res.messages.iter().fold(Uint128::zero(), |total, msg| match &msg.msg {
+        CosmosMsg::Bank(BankMsg::Send { to_address, amount }) if to_address == address => total + amount[0].amount,
+        _ => total,
+    })
+}
+
+#[test]
+fn native_pools_are_kept_per_denom() {
+    let (mut deps, env) = setup();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(1000, NATIVE), Coin::new(100, "usdt")]);
+    update_duration(&mut deps, &env, 100);
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+    let charge = ExecuteMsg::Charge { collection_address: collection() };
+    let err = execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &coins(100, "usdt")), charge.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidDenom {}));
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::UpdateCollectionDenoms {
+        collection_address: collection(),
+        native_denoms: vec![NATIVE.to_string(), "usdt".to_string()],
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &coins(100, "usdt")), charge).unwrap();
+
+    let usdt = Denom::Native("usdt".to_string());
+    let airdrop = |denom: Denom, amount: u128| ExecuteMsg::Airdrop {
+        collection_address: collection(),
+        denom,
+        airdrop_amount: Uint128::new(amount),
+    };
+    let err = execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), airdrop(usdt.clone(), 101)).unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientCw20 {}));
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), airdrop(usdt.clone(), 40)).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), airdrop(native(), 10)).unwrap();
+
+    let claim_denom = |denom: Denom| ExecuteMsg::Claim { collection_address: collection(), denom, owner: None, recipient: None };
+    let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), claim_denom(usdt.clone())).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: "alice".to_string(),
+        amount: coins(40, "usdt"),
+    }));
+    let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), claim_denom(usdt)).unwrap_err();
+    assert!(matches!(err, ContractError::NoReward {}));
+    assert_eq!(paid(&claim(&mut deps, &env, "alice").unwrap(), "alice"), Uint128::new(10));
+}
+
+#[test]
+fn accepted_owner_transfer_revokes_creator() {
+    let (mut deps, env) = setup();
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::ProposeOwner {
+        owner: Addr::unchecked("new_owner"),
+        expires: None,
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("new_owner", &[]), ExecuteMsg::AcceptOwner {}).unwrap();
+
+    let propose_back = ExecuteMsg::ProposeOwner { owner: Addr::unchecked("owner"), expires: None };
+    let err = execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), propose_back.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    execute(deps.as_mut(), env.clone(), mock_info("new_owner", &[]), propose_back).unwrap();
+}
+
+#[test]
+fn roles_gate_their_execute_paths() {
+    let (mut deps, env) = setup();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(1005, NATIVE));
+    let grant = |role: Role, address: &str| ExecuteMsg::GrantRole { role, address: Addr::unchecked(address) };
+    let register = ExecuteMsg::RegisteCollection {
+        collection_address: Addr::unchecked("collection_b"),
+        owner: Addr::unchecked(COLLECTION_OWNER),
+        new_unstaking_fee: Uint128::zero(),
+        new_unstaking_fee_percent: 0,
+        cw20_address: Addr::unchecked(""),
+        airdrop_mode: None,
+    };
+    let pause = ExecuteMsg::UpdatePause { collection_address: None, stake: true, claim: false, airdrop: false, withdraw: false };
+    let withdraw = |amount: u128| ExecuteMsg::Withdraw { denom: native(), amount: Uint128::new(amount) };
+
+    let err = execute(deps.as_mut(), env.clone(), mock_info("operator", &[]), grant(Role::Operator, "operator")).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    for role in Role::ALL {
+        execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), grant(role, role.as_str())).unwrap();
+    }
+
+    // each role only opens its own paths
+    for sender in ["pauser", "treasurer"] {
+        let err = execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), register.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+    execute(deps.as_mut(), env.clone(), mock_info("operator", &[]), register).unwrap();
+
+    for sender in ["operator", "treasurer"] {
+        let err = execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), pause.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+    execute(deps.as_mut(), env.clone(), mock_info("pauser", &[]), pause).unwrap();
+
+    for sender in ["operator", "pauser"] {
+        let err = execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), withdraw(5)).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+    let err = execute(deps.as_mut(), env.clone(), mock_info("treasurer", &[]), withdraw(6)).unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientFunds {}));
+    execute(deps.as_mut(), env.clone(), mock_info("treasurer", &[]), withdraw(5)).unwrap();
+
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::RevokeRole {
+        role: Role::Treasurer,
+        address: Addr::unchecked("treasurer"),
+    }).unwrap();
+    let err = execute(deps.as_mut(), env.clone(), mock_info("treasurer", &[]), withdraw(5)).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+}
+
+#[test]
+fn collection_managers_can_airdrop() {
+    let (mut deps, env) = setup();
+    update_duration(&mut deps, &env, 100);
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+    let airdrop = ExecuteMsg::Airdrop {
+        collection_address: collection(),
+        denom: native(),
+        airdrop_amount: Uint128::new(10),
+    };
+    let manager = |grant: bool| {
+        let address = Addr::unchecked("manager");
+        if grant {
+            ExecuteMsg::GrantCollectionManager { collection_address: collection(), address }
+        } else {
+            ExecuteMsg::RevokeCollectionManager { collection_address: collection(), address }
+        }
+    };
+
+    let err = execute(deps.as_mut(), env.clone(), mock_info("manager", &[]), airdrop.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    let err = execute(deps.as_mut(), env.clone(), mock_info("manager", &[]), manager(true)).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), manager(true)).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("manager", &[]), airdrop.clone()).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("manager", &[]), ExecuteMsg::AirdropRestart {
+        collection_address: collection(),
+    }).unwrap();
+
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), manager(false)).unwrap();
+    let err = execute(deps.as_mut(), env.clone(), mock_info("manager", &[]), airdrop).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+}
+
+#[test]
+fn collection_owner_cannot_pause_claims_or_withdrawals() {
+    let (mut deps, env) = setup();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(1000, NATIVE));
+    update_duration(&mut deps, &env, 100);
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+    let update_pause = |stake: bool, claim: bool, airdrop: bool, withdraw: bool| ExecuteMsg::UpdatePause {
+        collection_address: Some(collection()),
+        stake,
+        claim,
+        airdrop,
+        withdraw,
+    };
+    let airdrop = ExecuteMsg::Airdrop {
+        collection_address: collection(),
+        denom: native(),
+        airdrop_amount: Uint128::new(10),
+    };
+    let claim = ExecuteMsg::Claim { collection_address: collection(), denom: native(), owner: None, recipient: None };
+    let withdraw_airdrop = ExecuteMsg::WithdrawAirdrop {
+        collection_address: collection(),
+        denom: native(),
+        amount: Uint128::new(10),
+    };
+
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), update_pause(true, false, true, false)).unwrap();
+    let err = stake(&mut deps, &env, "bob", &["2"]).unwrap_err();
+    assert!(matches!(err, ContractError::Paused {}));
+    let err = execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), airdrop.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Paused {}));
+
+    for (claim_flag, withdraw_flag) in [(true, false), (false, true)] {
+        let err = execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), update_pause(false, claim_flag, false, withdraw_flag)).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), update_pause(false, false, false, false)).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), airdrop).unwrap();
+
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::GrantRole {
+        role: Role::Pauser,
+        address: Addr::unchecked("pauser"),
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("pauser", &[]), update_pause(false, true, false, true)).unwrap();
+    let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), claim.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Paused {}));
+    let err = execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), withdraw_airdrop.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Paused {}));
+    // the owner can still toggle its own flags but not lift the pauser's
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), update_pause(true, true, false, true)).unwrap();
+    let err = execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), update_pause(false, false, false, false)).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+
+    execute(deps.as_mut(), env.clone(), mock_info("pauser", &[]), update_pause(false, false, false, false)).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), claim).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), withdraw_airdrop).unwrap();
+}
+
+#[test]
+fn emergency_unstake_skips_fee_and_unbonding() {
+    let (mut deps, env) = setup();
+    update_duration(&mut deps, &env, 100);
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::UpdateCollectionFee {
+        collection_address: collection(),
+        new_unstaking_fee: Uint128::new(10),
+        new_unstaking_fee_percent: 0,
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::UpdateUnbondingDuration {
+        collection_address: collection(),
+        unbonding_duration: 100,
+    }).unwrap();
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+    let unstake = ExecuteMsg::Unstake {
+        collection_address: collection(),
+        token_id: vec!["1".to_string()],
+    };
+    let unstake_fee = |deps: &Deps| -> Uint128 {
+        let res: UnstakeFeeResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetUnstakeFee {
+            collection_address: collection(),
+            token_ids: vec!["1".to_string()],
+        }).unwrap()).unwrap();
+        res.total_fee
+    };
+    assert_eq!(unstake_fee(&deps), Uint128::new(10));
+
+    let emergency = ExecuteMsg::UpdateEmergencyUnstake { collection_address: Some(collection()), enabled: true };
+    let err = execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), emergency.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), emergency).unwrap();
+    assert_eq!(unstake_fee(&deps), Uint128::zero());
+
+    let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), unstake).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].msg, CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: COLLECTION.to_string(),
+        msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+            recipient: "alice".to_string(),
+            token_id: "1".to_string(),
+        }).unwrap(),
+        funds: vec![],
+    }));
+    assert!(UNBONDING_MAP.may_load(deps.as_ref().storage, (collection(), Addr::unchecked("alice"))).unwrap().unwrap_or_default().is_empty());
+}
+
+#[test]
+fn time_weighted_airdrop_follows_staked_time() {
+    let (mut deps, mut env) = setup();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(1000, NATIVE));
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::UpdateCollection {
+        collection_address: collection(),
+        new_cw20_address: Addr::unchecked(""),
+        new_duration: 1000,
+        new_fee_address: Addr::unchecked(COLLECTION_OWNER),
+        is_enabled: true,
+        airdrop_mode: Some(AirdropMode::TimeWeighted),
+    }).unwrap();
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+    env.block.time = env.block.time.plus_seconds(100);
+    stake(&mut deps, &env, "bob", &["2", "3"]).unwrap();
+    env.block.time = env.block.time.plus_seconds(200);
+    stake(&mut deps, &env, "carol", &["4"]).unwrap();
+
+    // alice has 300 nft-seconds, bob 2 * 200 and carol none yet
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::Airdrop {
+        collection_address: collection(),
+        denom: native(),
+        airdrop_amount: Uint128::new(700),
+    }).unwrap();
+    assert_eq!(paid(&claim(&mut deps, &env, "alice").unwrap(), "alice"), Uint128::new(300));
+    assert_eq!(paid(&claim(&mut deps, &env, "bob").unwrap(), "bob"), Uint128::new(400));
+    let err = claim(&mut deps, &env, "carol").unwrap_err();
+    assert!(matches!(err, ContractError::NoReward {}));
+}
+
+
+#[test]
+fn rarity_ranges_must_be_disjoint() {
+    let (mut deps, env) = setup();
+    let update_ranges = |ranges: Vec<(u64, u64, u64)>| ExecuteMsg::UpdateRarityWeights {
+        collection_address: collection(),
+        default_weight: None,
+        weights: vec![],
+        ranges: ranges
+            .into_iter()
+            .map(|(start, end, weight)| RarityRange { start, end, weight })
+            .collect(),
+    };
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), update_ranges(vec![(1, 100, 2)])).unwrap();
+
+    for overlapping in [(50, 60, 5), (100, 120, 5), (0, 1, 5)] {
+        let err = execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), update_ranges(vec![overlapping])).unwrap_err();
+        assert!(matches!(err, ContractError::OverlappingRange {}));
+    }
+    let err = execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), update_ranges(vec![(200, 300, 5), (250, 260, 5)])).unwrap_err();
+    assert!(matches!(err, ContractError::OverlappingRange {}));
+
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), update_ranges(vec![(1, 40, 3), (101, 110, 4)])).unwrap();
+    let weight = |token_id: &str| -> u64 {
+        let res: TokenWeightResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetTokenWeight {
+            collection_address: collection(),
+            token_id: token_id.to_string(),
+        }).unwrap()).unwrap();
+        res.weight
+    };
+    assert_eq!((weight("40"), weight("70"), weight("105")), (3, 1, 4));
+}
+
+#[test]
+fn merkle_airdrop_claims() {
+    let (mut deps, mut env) = setup();
+    let leaf = |address: &str, amount: u128| util::merkle_leaf(&Addr::unchecked(address), Uint128::new(amount));
+    let (alice, bob, carol) = (leaf("alice", 30), leaf("bob", 20), leaf("carol", 10));
+    let root = util::merkle_node(util::merkle_node(alice, bob), carol);
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::CreateMerkleAirdrop {
+        collection_address: collection(),
+        denom: native(),
+        merkle_root: hex::encode(root),
+        total_amount: Uint128::new(60),
+        expiration: Expiration::AtHeight(env.block.height + 10),
+    }).unwrap();
+
+    let claim = |amount: u128, proof: Vec<[u8; 32]>| ExecuteMsg::ClaimMerkleAirdrop {
+        airdrop_id: 1,
+        amount: Uint128::new(amount),
+        proof: proof.into_iter().map(hex::encode).collect(),
+    };
+    let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), claim(30, vec![bob, carol])).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: "alice".to_string(),
+        amount: coins(30, NATIVE),
+    }));
+    let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), claim(30, vec![bob, carol])).unwrap_err();
+    assert!(matches!(err, ContractError::MerkleClaimed {}));
+
+    // wrong amount, someone else's proof, and a node passed off as a leaf
+    let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), claim(25, vec![alice, carol])).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidMerkleProof {}));
+    let err = execute(deps.as_mut(), env.clone(), mock_info("mallory", &[]), claim(20, vec![alice, carol])).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidMerkleProof {}));
+    let err = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), claim(10, vec![])).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidMerkleProof {}));
+    execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), claim(10, vec![util::merkle_node(alice, bob)])).unwrap();
+
+    let reclaim = ExecuteMsg::ReclaimMerkleAirdrop { airdrop_id: 1 };
+    let err = execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), reclaim.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::MerkleNotExpired {}));
+    env.block.height += 10;
+    let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), claim(20, vec![alice, carol])).unwrap_err();
+    assert!(matches!(err, ContractError::MerkleExpired {}));
+
+    let err = execute(deps.as_mut(), env.clone(), mock_info("mallory", &[]), reclaim.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), reclaim.clone()).unwrap();
+    let err = execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), reclaim).unwrap_err();
+    assert!(matches!(err, ContractError::NoReward {}));
+    let res: CollectionResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetCollection {
+        collection_address: collection(),
+    }).unwrap()).unwrap();
+    assert_eq!(res.airdrop_infos[0].airdropable, Uint128::new(1000 - 30 - 10));
+}
+
+
+fn set_auto_relock(deps: &mut Deps, env: &Env, token_ids: Option<&[&str]>, enabled: bool) {
+    execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::SetAutoRelock {
+        collection_address: collection(),
+        token_ids: token_ids.map(|token_ids| token_ids.iter().map(|id| id.to_string()).collect()),
+        enabled,
+    }).unwrap();
+}
+
+#[test]
+fn position_auto_relock_overrides_user_setting() {
+    let (mut deps, mut env) = setup();
+    update_duration(&mut deps, &env, 100);
+    let start = env.block.time.seconds();
+    stake(&mut deps, &env, "alice", &["1", "2"]).unwrap();
+    set_auto_relock(&mut deps, &env, None, true);
+    set_auto_relock(&mut deps, &env, Some(&["2"]), false);
+
+    env.block.time = env.block.time.plus_seconds(150);
+    let nfts = staked_nfts(&deps, &env, "alice").nft_maps;
+    assert_eq!((nfts[0].auto_relock, nfts[0].lock_time), (true, start + 200));
+    assert_eq!((nfts[1].auto_relock, nfts[1].lock_time), (false, start + 100));
+
+    // switching the user setting off leaves the position override alone
+    set_auto_relock(&mut deps, &env, Some(&["2"]), true);
+    set_auto_relock(&mut deps, &env, None, false);
+    env.block.time = env.block.time.plus_seconds(100);
+    let nfts = staked_nfts(&deps, &env, "alice").nft_maps;
+    assert_eq!((nfts[0].auto_relock, nfts[0].lock_time), (false, start + 200));
+    assert_eq!((nfts[1].auto_relock, nfts[1].lock_time), (true, start + 300));
+}
+
+#[test]
+fn auto_relock_on_expired_position_keeps_its_schedule() {
+    let (mut deps, mut env) = setup();
+    update_duration(&mut deps, &env, 100);
+    let start = env.block.time.seconds();
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+
+    env.block.time = env.block.time.plus_seconds(150);
+    set_auto_relock(&mut deps, &env, Some(&["1"]), true);
+    assert_eq!(staked_nfts(&deps, &env, "alice").nft_maps[0].lock_time, start + 200);
+
+    env.block.time = env.block.time.plus_seconds(100);
+    set_auto_relock(&mut deps, &env, None, true);
+    assert_eq!(staked_nfts(&deps, &env, "alice").nft_maps[0].lock_time, start + 300);
+}
+
+
+#[test]
+fn lock_tier_updates_keep_existing_positions() {
+    let (mut deps, env) = setup();
+    let update_lock_tiers = |duration: u64, unstaking_fee: u128| ExecuteMsg::UpdateLockTiers {
+        collection_address: collection(),
+        lock_tiers: vec![LockTier { duration, multiplier: 2, unstaking_fee: Uint128::new(unstaking_fee) }],
+    };
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), update_lock_tiers(100, 50)).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(1, NATIVE)), ExecuteMsg::Staking {
+        collection_address: collection(),
+        token_id: vec!["1".to_string()],
+        tier: Some(0),
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), update_lock_tiers(10, 5)).unwrap();
+
+    let nft = &staked_nfts(&deps, &env, "alice").nft_maps[0];
+    assert_eq!(nft.lock_time, env.block.time.seconds() + 100);
+    let res: UnstakeFeeResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetUnstakeFee {
+        collection_address: collection(),
+        token_ids: vec!["1".to_string()],
+    }).unwrap()).unwrap();
+    assert_eq!(res.total_fee, Uint128::new(50));
+}
+
+#[test]
+fn staking_into_each_lock_tier() {
+    let (mut deps, env) = setup();
+    update_duration(&mut deps, &env, 50);
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::UpdateCollectionFee {
+        collection_address: collection(),
+        new_unstaking_fee: Uint128::new(5),
+        new_unstaking_fee_percent: 0,
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::UpdateLockTiers {
+        collection_address: collection(),
+        lock_tiers: vec![
+            LockTier { duration: 100, multiplier: 2, unstaking_fee: Uint128::new(10) },
+            LockTier { duration: 200, multiplier: 3, unstaking_fee: Uint128::new(20) },
+        ],
+    }).unwrap();
+    let stake_tier = |deps: &mut Deps, token_id: &str, tier: Option<u32>| {
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(1, NATIVE)), ExecuteMsg::Staking {
+            collection_address: collection(),
+            token_id: vec![token_id.to_string()],
+            tier,
+        })
+    };
+    stake_tier(&mut deps, "1", None).unwrap();
+    stake_tier(&mut deps, "2", Some(0)).unwrap();
+    stake_tier(&mut deps, "3", Some(1)).unwrap();
+    let err = stake_tier(&mut deps, "4", Some(2)).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidTier {}));
+
+    let now = env.block.time.seconds();
+    let nfts = staked_nfts(&deps, &env, "alice").nft_maps;
+    let positions = nfts.iter().map(|nft| (nft.tier, nft.lock_time, nft.multiplier)).collect::<Vec<_>>();
+    assert_eq!(positions, vec![(None, now + 50, 0), (Some(0), now + 100, 2), (Some(1), now + 200, 3)]);
+    for (token_id, fee) in [("1", 5), ("2", 10), ("3", 20)] {
+        let res: UnstakeFeeResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetUnstakeFee {
+            collection_address: collection(),
+            token_ids: vec![token_id.to_string()],
+        }).unwrap()).unwrap();
+        assert_eq!(res.total_fee, Uint128::new(fee));
+    }
+    assert_eq!(STREAM_MAP.load(deps.as_ref().storage, collection()).unwrap().total_shares, Uint128::new(1 + 2 + 3));
+}
+
+#[test]
+fn staking_rejects_unbonding_nfts() {
+    let (mut deps, env) = setup();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::UpdateUnbondingDuration {
+        collection_address: collection(),
+        unbonding_duration: 100,
+    }).unwrap();
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::Unstake {
+        collection_address: collection(),
+        token_id: vec!["1".to_string()],
+    }).unwrap();
+
+    let err = stake(&mut deps, &env, "alice", &["1"]).unwrap_err();
+    assert!(matches!(err, ContractError::AlreadyStaked {}));
+}
+
+fn unstake_fee_at(deps: &Deps, env: &Env, seconds: u64) -> Uint128 {
+    let mut env = env.clone();
+    env.block.time = env.block.time.plus_seconds(seconds);
+    let res: UnstakeFeeResponse = from_binary(&query(deps.as_ref(), env, QueryMsg::GetUnstakeFee {
+        collection_address: collection(),
+        token_ids: vec!["1".to_string()],
+    }).unwrap()).unwrap();
+    res.total_fee
+}
+
+fn setup_fee_curve(fee_curve: FeeCurve) -> (Deps, Env) {
+    let (mut deps, env) = setup();
+    update_duration(&mut deps, &env, 100);
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::UpdateCollectionFee {
+        collection_address: collection(),
+        new_unstaking_fee: Uint128::new(100),
+        new_unstaking_fee_percent: 0,
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::UpdateFeeCurve {
+        collection_address: collection(),
+        fee_curve,
+    }).unwrap();
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+    (deps, env)
+}
+
+#[test]
+fn linear_fee_curve_decays_to_expiry() {
+    let (deps, env) = setup_fee_curve(FeeCurve::Linear);
+    assert_eq!(unstake_fee_at(&deps, &env, 0), Uint128::new(100));
+    assert_eq!(unstake_fee_at(&deps, &env, 50), Uint128::new(50));
+    assert_eq!(unstake_fee_at(&deps, &env, 99), Uint128::new(1));
+    assert_eq!(unstake_fee_at(&deps, &env, 100), Uint128::zero());
+}
+
+#[test]
+fn stepwise_fee_curve_follows_brackets() {
+    let (deps, env) = setup_fee_curve(FeeCurve::Stepwise {
+        brackets: vec![
+            FeeBracket { min_remaining: 1, percent: 25 },
+            FeeBracket { min_remaining: 50, percent: 80 },
+        ],
+    });
+    assert_eq!(unstake_fee_at(&deps, &env, 0), Uint128::new(80));
+    assert_eq!(unstake_fee_at(&deps, &env, 50), Uint128::new(80));
+    assert_eq!(unstake_fee_at(&deps, &env, 51), Uint128::new(25));
+    assert_eq!(unstake_fee_at(&deps, &env, 100), Uint128::zero());
+}
+
+#[test]
+fn invalid_fee_curves_are_rejected() {
+    let (mut deps, env) = setup();
+    let update_fee_curve = |brackets: Vec<(u64, u64)>| ExecuteMsg::UpdateFeeCurve {
+        collection_address: collection(),
+        fee_curve: FeeCurve::Stepwise {
+            brackets: brackets
+                .into_iter()
+                .map(|(min_remaining, percent)| FeeBracket { min_remaining, percent })
+                .collect(),
+        },
+    };
+    for brackets in [vec![], vec![(10, 101)], vec![(50, 80), (10, 25)], vec![(10, 25), (10, 80)]] {
+        let err = execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), update_fee_curve(brackets)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFeeCurve {}));
+    }
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), update_fee_curve(vec![(10, 25), (50, 100)])).unwrap();
+}
+
+#[test]
+fn cw20_fee_denom_charges_collection_tx_fee() {
+    let (mut deps, env) = setup();
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::UpdateFeeDenom {
+        collection_address: collection(),
+        fee_denom: Some(Denom::Cw20(Addr::unchecked("project"))),
+        tx_fee: Uint128::new(1_000_000),
+    }).unwrap();
+    let res: CollectionResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetCollection {
+        collection_address: collection(),
+    }).unwrap()).unwrap();
+    assert_eq!(res.tx_fee, Uint128::new(1_000_000));
+
+    let stake_with = |amount: u128| ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "alice".to_string(),
+        amount: Uint128::new(amount),
+        msg: to_binary(&TokenReceiveMsg::Staking {
+            collection_address: collection(),
+            token_id: vec!["1".to_string()],
+            tier: None,
+        }).unwrap(),
+    });
+    let err = execute(deps.as_mut(), env.clone(), mock_info("project", &[]), stake_with(1)).unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientCw20 {}));
+    execute(deps.as_mut(), env.clone(), mock_info("project", &[]), stake_with(1_000_000)).unwrap();
+
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::UpdateFeeDenom {
+        collection_address: collection(),
+        fee_denom: None,
+        tx_fee: Uint128::new(1_000_000),
+    }).unwrap();
+    stake(&mut deps, &env, "bob", &["2"]).unwrap();
+}
+
+fn send_nft(deps: &mut Deps, env: &Env, sender: &str, token_id: &str) -> Result<Response, ContractError> {
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION, &[]), ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: sender.to_string(),
+        token_id: token_id.to_string(),
+        msg: to_binary(&NftReceiveMsg::Stake {
+            sender: sender.to_string(),
+            token_id: token_id.to_string(),
+            collection_address: collection(),
+            tier: None,
+        }).unwrap(),
+    }))
+}
+
+#[test]
+fn send_nft_charges_collection_tx_fee() {
+    let (mut deps, env) = setup();
+    execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(10, NATIVE)), ExecuteMsg::DepositTxFee {}).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::UpdateFeeDenom {
+        collection_address: collection(),
+        fee_denom: Some(native()),
+        tx_fee: Uint128::new(4),
+    }).unwrap();
+
+    let res = send_nft(&mut deps, &env, "alice", "1").unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: "fee".to_string(),
+        amount: coins(4, NATIVE),
+    }));
+    assert_eq!(FEE_CREDIT_MAP.load(deps.as_ref().storage, Addr::unchecked("alice")).unwrap(), Uint128::new(6));
+    send_nft(&mut deps, &env, "alice", "2").unwrap();
+    let err = send_nft(&mut deps, &env, "alice", "3").unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientCw20 {}));
+    assert_eq!(staked_nfts(&deps, &env, "alice").nft_maps.len(), 2);
+}
+
+#[test]
+fn send_nft_rejects_cw20_fee_denom() {
+    let (mut deps, env) = setup();
+    execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(10, NATIVE)), ExecuteMsg::DepositTxFee {}).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::UpdateFeeDenom {
+        collection_address: collection(),
+        fee_denom: Some(Denom::Cw20(Addr::unchecked("project"))),
+        tx_fee: Uint128::new(4),
+    }).unwrap();
+
+    let err = send_nft(&mut deps, &env, "alice", "1").unwrap_err();
+    assert!(matches!(err, ContractError::InvalidDenom {}));
+    assert_eq!(FEE_CREDIT_MAP.load(deps.as_ref().storage, Addr::unchecked("alice")).unwrap(), Uint128::new(10));
+    assert!(staked_nfts(&deps, &env, "alice").nft_maps.is_empty());
+}
+
+
+#[test]
+fn overpaid_fees_are_refunded() {
+    let (mut deps, env) = setup();
+    let staking = |token_id: &str| ExecuteMsg::Staking {
+        collection_address: collection(),
+        token_id: vec![token_id.to_string()],
+        tier: None,
+    };
+    let refund_to_alice = |amount: u128| CosmosMsg::Bank(BankMsg::Send {
+        to_address: "alice".to_string(),
+        amount: coins(amount, NATIVE),
+    });
+
+    let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(1, NATIVE)), staking("1")).unwrap();
+    assert!(!res.messages.iter().any(|msg| msg.msg == refund_to_alice(1)));
+    assert!(!res.attributes.iter().any(|attr| attr.key == "refund"));
+
+    let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, NATIVE)), staking("2")).unwrap();
+    assert!(res.messages.iter().any(|msg| msg.msg == refund_to_alice(4)));
+    assert!(res.attributes.iter().any(|attr| attr.key == "refund" && attr.value == "4"));
+
+    let funds = vec![Coin::new(1, NATIVE), Coin::new(1, "usdt")];
+    let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &funds), staking("3")).unwrap_err();
+    assert!(matches!(err, ContractError::Payment(PaymentError::MultipleDenoms {})));
+    let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(1, "usdt")), staking("3")).unwrap_err();
+    assert!(matches!(err, ContractError::Payment(PaymentError::MissingDenom(_))));
+
+    // nothing is due on an expired lock, so any payment is refused
+    let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(1, NATIVE)), ExecuteMsg::Unstake {
+        collection_address: collection(),
+        token_id: vec!["1".to_string()],
+    }).unwrap_err();
+    assert!(matches!(err, ContractError::Payment(PaymentError::NonPayable {})));
+}
+
+#[test]
+fn unstaking_fee_percent_is_bounded() {
+    let (mut deps, env) = setup();
+    let err = execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::RegisteCollection {
+        collection_address: Addr::unchecked("other"),
+        owner: Addr::unchecked(COLLECTION_OWNER),
+        new_unstaking_fee: Uint128::new(10),
+        new_unstaking_fee_percent: 101,
+        cw20_address: Addr::unchecked(""),
+        airdrop_mode: None,
+    }).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidFeePercent {}));
+
+    let update_fee = |percent: u64| ExecuteMsg::UpdateCollectionFee {
+        collection_address: collection(),
+        new_unstaking_fee: Uint128::new(10),
+        new_unstaking_fee_percent: percent,
+    };
+    let err = execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), update_fee(101)).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidFeePercent {}));
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), update_fee(100)).unwrap();
+}
+
+#[test]
+fn ledger_follows_liabilities() {
+    let (mut deps, mut env) = setup();
+    let check_ledger = |deps: &Deps| {
+        let collection = COLLECTION_MAP.load(deps.as_ref().storage, collection()).unwrap();
+        let ledger = COLLECTION_FUNDS_MAP.load(deps.as_ref().storage, collection.address.clone()).unwrap();
+        assert_eq!(ledger, util::collection_liabilities(deps.as_ref().storage, &collection).unwrap());
+    };
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::UpdateCollection {
+        collection_address: collection(),
+        new_cw20_address: Addr::unchecked(""),
+        new_duration: 100,
+        new_fee_address: Addr::unchecked(COLLECTION_OWNER),
+        is_enabled: true,
+        airdrop_mode: None,
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::ChargeStream {
+        collection_address: collection(),
+        denom: native(),
+        amount: Uint128::new(100),
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::UpdateStreamRate {
+        collection_address: collection(),
+        reward_per_second: Uint128::new(1),
+    }).unwrap();
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+    stake(&mut deps, &env, "bob", &["2", "3"]).unwrap();
+    check_ledger(&deps);
+
+    env.block.time = env.block.time.plus_seconds(7);
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::Airdrop {
+        collection_address: collection(),
+        denom: native(),
+        airdrop_amount: Uint128::new(31),
+    }).unwrap();
+    check_ledger(&deps);
+
+    execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::Claim {
+        collection_address: collection(),
+        denom: native(),
+        owner: None,
+        recipient: None,
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::WithdrawStream {
+        collection_address: collection(),
+        amount: Uint128::new(10),
+    }).unwrap();
+    env.block.time = env.block.time.plus_seconds(200);
+    execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), ExecuteMsg::Unstake {
+        collection_address: collection(),
+        token_id: vec!["2".to_string(), "3".to_string()],
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), ExecuteMsg::Claim {
+        collection_address: collection(),
+        denom: native(),
+        owner: None,
+        recipient: None,
+    }).unwrap();
+    check_ledger(&deps);
+}
+
+#[test]
+fn audit_counts_stream_rounding_dust() {
+    let (mut deps, mut env) = setup();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::ChargeStream {
+        collection_address: collection(),
+        denom: native(),
+        amount: Uint128::new(10),
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::UpdateStreamRate {
+        collection_address: collection(),
+        reward_per_second: Uint128::new(1),
+    }).unwrap();
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+    stake(&mut deps, &env, "bob", &["2", "3"]).unwrap();
+    env.block.time = env.block.time.plus_seconds(10);
+
+    // 10 over 3 shares floors to 3 and 6, leaving 1 unpaid.
+    for user in ["alice", "bob"] {
+        execute(deps.as_mut(), env.clone(), mock_info(user, &[]), ExecuteMsg::Claim {
+            collection_address: collection(),
+            denom: native(),
+            owner: None,
+            recipient: None,
+        }).unwrap();
+    }
+    let res: AuditResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Audit {
+        start_after: None,
+        limit: None,
+    }).unwrap()).unwrap();
+    let audit = &res.collections[0];
+    assert!(audit.in_sync, "{:?}", audit);
+    assert!(audit.liabilities.iter().any(|fund| fund.amount == Uint128::new(1000 - 10 + 1)), "{:?}", audit);
+}
+
+
+#[test]
+fn audit_reports_unqueryable_denoms() {
+    let (mut deps, env) = setup();
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), ExecuteMsg::UpdateCollectionCw20Denoms {
+        collection_address: collection(),
+        cw20_denoms: vec![Addr::unchecked("not_a_cw20")],
+    }).unwrap();
+    charge_cw20(&mut deps, &env, "not_a_cw20", 100).unwrap();
+
+    let res: AuditResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Audit {
+        start_after: None,
+        limit: None,
+    }).unwrap()).unwrap();
+    let native_audit = res.denoms.iter().find(|audit| audit.denom == native()).unwrap();
+    assert_eq!(native_audit.balance, Some(Uint128::zero()));
+    assert_eq!(native_audit.shortfall, Some(Uint128::new(1000)));
+    assert!(native_audit.error.is_none());
+    let cw20_audit = res.denoms.iter().find(|audit| audit.denom == Denom::Cw20(Addr::unchecked("not_a_cw20"))).unwrap();
+    assert_eq!(cw20_audit.liabilities, Uint128::new(100));
+    assert!(cw20_audit.balance.is_none() && cw20_audit.shortfall.is_none());
+    assert!(cw20_audit.error.is_some());
+    assert_eq!(res.collections.len(), 1);
+}
+
+#[test]
+fn claim_all_across_collections() {
+    let (mut deps, env) = setup();
+    register_collection(&mut deps, &env, "collection_b");
+    register_collection(&mut deps, &env, "collection_c");
+    for collection_address in [COLLECTION, "collection_b", "collection_c"] {
+        execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::UpdateCollection {
+            collection_address: Addr::unchecked(collection_address),
+            new_cw20_address: Addr::unchecked(""),
+            new_duration: 100,
+            new_fee_address: Addr::unchecked(COLLECTION_OWNER),
+            is_enabled: true,
+            airdrop_mode: None,
+        }).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(1, NATIVE)), ExecuteMsg::Staking {
+            collection_address: Addr::unchecked(collection_address),
+            token_id: vec!["1".to_string()],
+            tier: None,
+        }).unwrap();
+    }
+    for (collection_address, amount) in [(COLLECTION, 30), ("collection_b", 20)] {
+        execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::Airdrop {
+            collection_address: Addr::unchecked(collection_address),
+            denom: native(),
+            airdrop_amount: Uint128::new(amount),
+        }).unwrap();
+    }
+    let ledger = |deps: &Deps, collection_address: &str| {
+        COLLECTION_FUNDS_MAP.load(deps.as_ref().storage, Addr::unchecked(collection_address)).unwrap()
+    };
+    let claim_all = |collection_addresses: Option<Vec<&str>>| ExecuteMsg::ClaimAll {
+        collection_addresses: collection_addresses.map(|addresses| addresses.into_iter().map(Addr::unchecked).collect()),
+        owner: None,
+        recipient: None,
+    };
+    let paid = |res: &Response| res.messages.iter().map(|msg| msg.msg.clone()).collect::<Vec<_>>();
+    let pay_alice = |amount: u128| CosmosMsg::Bank(BankMsg::Send {
+        to_address: "alice".to_string(),
+        amount: coins(amount, NATIVE),
+    });
+
+    let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), claim_all(Some(vec!["collection_b", "collection_c"]))).unwrap();
+    assert_eq!(paid(&res), vec![pay_alice(20)]);
+    assert_eq!(res.events.len(), 1);
+    assert_eq!(ledger(&deps, "collection_b"), vec![FundInfo { denom: native(), amount: Uint128::new(1000 - 20) }]);
+    assert_eq!(ledger(&deps, COLLECTION), vec![FundInfo { denom: native(), amount: Uint128::new(1000) }]);
+
+    let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), claim_all(None)).unwrap();
+    assert_eq!(paid(&res), vec![pay_alice(30)]);
+    assert_eq!(ledger(&deps, COLLECTION), vec![FundInfo { denom: native(), amount: Uint128::new(1000 - 30) }]);
+    assert_eq!(ledger(&deps, "collection_c"), vec![FundInfo { denom: native(), amount: Uint128::new(1000) }]);
+
+    let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), claim_all(None)).unwrap_err();
+    assert!(matches!(err, ContractError::NoReward {}));
+    let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), claim_all(Some(vec![COLLECTION]))).unwrap_err();
+    assert!(matches!(err, ContractError::NoReward {}));
+}
+
+
+#[test]
+fn withdraw_vested_pays_claim_recipient() {
+    let (mut deps, mut env) = setup();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::UpdateCollectionVesting {
+        collection_address: collection(),
+        vesting: Some(Vesting { cliff: 0, duration: 10 }),
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::UpdateCollection {
+        collection_address: collection(),
+        new_cw20_address: Addr::unchecked(""),
+        new_duration: 100,
+        new_fee_address: Addr::unchecked(COLLECTION_OWNER),
+        is_enabled: true,
+        airdrop_mode: None,
+    }).unwrap();
+    stake(&mut deps, &env, "alice", &["1"]).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(COLLECTION_OWNER, &[]), ExecuteMsg::Airdrop {
+        collection_address: collection(),
+        denom: native(),
+        airdrop_amount: Uint128::new(40),
+    }).unwrap();
+    let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::Claim {
+        collection_address: collection(),
+        denom: native(),
+        owner: None,
+        recipient: None,
+    }).unwrap();
+    assert!(res.messages.is_empty());
+
+    execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::SetWithdrawAddress {
+        address: Some(Addr::unchecked("vault")),
+    }).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::ApproveClaimOperator {
+        operator: Addr::unchecked("bot"),
+        expires: None,
+    }).unwrap();
+    env.block.time = env.block.time.plus_seconds(10);
+
+    let withdraw = |recipient: Option<&str>| ExecuteMsg::WithdrawVested {
+        collection_address: collection(),
+        denom: None,
+        owner: Some(Addr::unchecked("alice")),
+        recipient: recipient.map(Addr::unchecked),
+    };
+    let err = execute(deps.as_mut(), env.clone(), mock_info("bot", &[]), withdraw(Some("bot"))).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    let res = execute(deps.as_mut(), env.clone(), mock_info("bot", &[]), withdraw(None)).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: "vault".to_string(),
+        amount: coins(40, NATIVE),
+    }));
+}
@@ -1,14 +1,57 @@
 use std::convert::{From, TryFrom};
 use cosmwasm_std::{
     to_binary,  Response, StdResult, Uint128, Coin, BankMsg,
-    WasmMsg, WasmQuery, QueryRequest, Addr, Storage, CosmosMsg,  QuerierWrapper, BalanceResponse as NativeBalanceResponse, BankQuery, Order, BlockInfo
+    WasmMsg, WasmQuery, QueryRequest, Addr, Storage, CosmosMsg,  QuerierWrapper, BalanceResponse as NativeBalanceResponse, BankQuery, Order, BlockInfo,
+    Decimal256, Uint256, MessageInfo,
 };
+use cw_storage_plus::Bound;
+use cw_utils::{must_pay, nonpayable, Expiration, PaymentError};
+use sha2::{Digest, Sha256};
 use cw20::{Cw20ExecuteMsg, Denom, BalanceResponse as CW20BalanceResponse, Cw20QueryMsg};
 use crate::error::ContractError;
+use crate::msg::TokenWeight;
 use crate::state::{
+    Config,
     CONFIG,
     COLLECTION_MAP,
     Collection,
+    AirdropMode,
+    AirdropInfo,
+    NftInfo,
+    UserInfo,
+    EarnInfo,
+    StreamInfo,
+    STREAM_MAP,
+    PendingOwner,
+    PENDING_OWNER,
+    PENDING_COLLECTION_OWNER_MAP,
+    Role,
+    ROLE_MAP,
+    COLLECTION_MANAGER_MAP,
+    PauseInfo,
+    GLOBAL_PAUSE,
+    COLLECTION_PAUSE_MAP,
+    RarityRange,
+    RARITY_DEFAULT_MAP,
+    RARITY_WEIGHT_MAP,
+    RARITY_RANGE_MAP,
+    Vesting,
+    VestingInfo,
+    LockTier,
+    FeeCurve,
+    RevenueInfo,
+    REVENUE_MAP,
+    FundInfo,
+    COLLECTION_FUNDS_MAP,
+    ALLOCATED_FUNDS,
+    VESTING_MAP,
+    AUTO_RELOCK_MAP,
+    MERKLE_AIRDROP_MAP,
+    OPEN_MERKLE_AIRDROP_MAP,
+    CLAIM_OPERATOR_MAP,
+    WITHDRAW_ADDRESS_MAP,
+    staked_nft_map,
+    user_map,
 };
 
 pub fn check_owner(
@@ -25,6 +68,18 @@ pub fn check_owner(
     )
 }
 
+/// The owner and creator hold every role implicitly.
+pub fn check_role(
+    storage: &mut dyn Storage,
+    address: Addr,
+    role: Role,
+) -> Result<Response, ContractError> {
+    if check_owner(storage, address.clone()).is_err() && !ROLE_MAP.has(storage, (role.as_str().to_string(), address)) {
+        return Err(ContractError::Unauthorized {  })
+    }
+    Ok(Response::new().add_attribute("action", "check_role"))
+}
+
 pub fn check_enabled(
     storage: &mut dyn Storage,
     collection_address: Addr
@@ -60,21 +115,351 @@ pub fn check_collection_owner(
     Ok(Response::new().add_attribute("action", "check_collection_owner"))
 }
 
-pub fn execute_update_owner(
+/// Managers run airdrops for a collection but cannot touch its fees or funds.
+pub fn check_collection_manager(
+    storage: &mut dyn Storage,
+    collection_address: Addr,
+    address: Addr
+) -> Result<Response, ContractError> {
+    if check_collection_owner(storage, collection_address.clone(), address.clone()).is_err() 
+        && !COLLECTION_MANAGER_MAP.has(storage, (collection_address, address)) {
+        return Err(ContractError::Unauthorized {  })
+    }
+    Ok(Response::new().add_attribute("action", "check_collection_manager"))
+}
+
+/// Global flags combined with the collection's own, if one is given.
+pub fn load_pause(
+    storage: &dyn Storage,
+    collection_address: Option<Addr>,
+) -> StdResult<PauseInfo> {
+    let global = GLOBAL_PAUSE.may_load(storage)?.unwrap_or_default();
+    match collection_address {
+        Some(collection_address) => {
+            let collection = COLLECTION_PAUSE_MAP.may_load(storage, collection_address)?.unwrap_or_default();
+            Ok(global.merge(&collection))
+        },
+        None => Ok(global),
+    }
+}
+
+pub fn check_not_paused(
+    storage: &dyn Storage,
+    collection_address: Option<Addr>,
+    paused: fn(&PauseInfo) -> bool,
+) -> Result<Response, ContractError> {
+    if paused(&load_pause(storage, collection_address)?) {
+        return Err(ContractError::Paused {  })
+    }
+    Ok(Response::new().add_attribute("action", "check_not_paused"))
+}
+
+/// Pausers act globally, collection owners only on their own collection and
+/// only on `stake` and `airdrop`, so they can't freeze stakers' rewards.
+pub fn execute_update_pause(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Option<Addr>,
+    stake: bool,
+    claim: bool,
+    airdrop: bool,
+    withdraw: bool,
+) -> Result<Response, ContractError> {
+    let update = |mut pause: PauseInfo| -> PauseInfo {
+        pause.stake = stake;
+        pause.claim = claim;
+        pause.airdrop = airdrop;
+        pause.withdraw = withdraw;
+        pause
+    };
+
+    match collection_address.clone() {
+        Some(collection_address) => {
+            if !COLLECTION_MAP.has(storage, collection_address.clone()) {
+                return Err(ContractError::InvalidCollection {  });
+            }
+            let pause = COLLECTION_PAUSE_MAP.may_load(storage, collection_address.clone())?.unwrap_or_default();
+            if check_role(storage, address.clone(), Role::Pauser).is_err() {
+                check_collection_owner(storage, collection_address.clone(), address)?;
+                if claim != pause.claim || withdraw != pause.withdraw {
+                    return Err(ContractError::Unauthorized {  });
+                }
+            }
+            COLLECTION_PAUSE_MAP.save(storage, collection_address, &update(pause))?;
+        },
+        None => {
+            check_role(storage, address, Role::Pauser)?;
+            let pause = GLOBAL_PAUSE.may_load(storage)?.unwrap_or_default();
+            GLOBAL_PAUSE.save(storage, &update(pause))?;
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "update_pause")
+        .add_attribute("collection_address", collection_address.map(String::from).unwrap_or_default())
+        .add_attribute("stake", stake.to_string())
+        .add_attribute("claim", claim.to_string())
+        .add_attribute("airdrop", airdrop.to_string())
+        .add_attribute("withdraw", withdraw.to_string())
+    )
+}
+
+pub fn execute_update_emergency_unstake(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Option<Addr>,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    check_owner(storage, address)?;
+
+    match collection_address.clone() {
+        Some(collection_address) => {
+            if !COLLECTION_MAP.has(storage, collection_address.clone()) {
+                return Err(ContractError::InvalidCollection {  });
+            }
+            let mut pause = COLLECTION_PAUSE_MAP.may_load(storage, collection_address.clone())?.unwrap_or_default();
+            pause.emergency_unstake = enabled;
+            COLLECTION_PAUSE_MAP.save(storage, collection_address, &pause)?;
+        },
+        None => {
+            let mut pause = GLOBAL_PAUSE.may_load(storage)?.unwrap_or_default();
+            pause.emergency_unstake = enabled;
+            GLOBAL_PAUSE.save(storage, &pause)?;
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "update_emergency_unstake")
+        .add_attribute("collection_address", collection_address.map(String::from).unwrap_or_default())
+        .add_attribute("enabled", enabled.to_string())
+    )
+}
+
+pub fn execute_grant_role(
+    storage: &mut dyn Storage,
+    sender: Addr,
+    role: Role,
+    address: Addr,
+) -> Result<Response, ContractError> {
+    check_owner(storage, sender)?;
+
+    ROLE_MAP.save(storage, (role.as_str().to_string(), address.clone()), &true)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "grant_role")
+        .add_attribute("role", role.as_str())
+        .add_attribute("address", address)
+    )
+}
+
+pub fn execute_revoke_role(
+    storage: &mut dyn Storage,
+    sender: Addr,
+    role: Role,
+    address: Addr,
+) -> Result<Response, ContractError> {
+    check_owner(storage, sender)?;
+
+    ROLE_MAP.remove(storage, (role.as_str().to_string(), address.clone()));
+
+    Ok(Response::new()
+        .add_attribute("action", "revoke_role")
+        .add_attribute("role", role.as_str())
+        .add_attribute("address", address)
+    )
+}
+
+pub fn execute_grant_collection_manager(
+    storage: &mut dyn Storage,
+    sender: Addr,
+    collection_address: Addr,
+    address: Addr,
+) -> Result<Response, ContractError> {
+    check_collection_owner(storage, collection_address.clone(), sender)?;
+
+    COLLECTION_MANAGER_MAP.save(storage, (collection_address.clone(), address.clone()), &true)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "grant_collection_manager")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("address", address)
+    )
+}
+
+pub fn execute_revoke_collection_manager(
+    storage: &mut dyn Storage,
+    sender: Addr,
+    collection_address: Addr,
+    address: Addr,
+) -> Result<Response, ContractError> {
+    check_collection_owner(storage, collection_address.clone(), sender)?;
+
+    COLLECTION_MANAGER_MAP.remove(storage, (collection_address.clone(), address.clone()));
+
+    Ok(Response::new()
+        .add_attribute("action", "revoke_collection_manager")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("address", address)
+    )
+}
+
+pub fn execute_approve_claim_operator(
+    storage: &mut dyn Storage,
+    block: BlockInfo,
+    address: Addr,
+    operator: Addr,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&block) {
+        return Err(ContractError::ClaimOperatorExpired {  });
+    }
+    CLAIM_OPERATOR_MAP.save(storage, (address.clone(), operator.clone()), &expires)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "approve_claim_operator")
+        .add_attribute("owner", address)
+        .add_attribute("operator", operator)
+        .add_attribute("expires", expires.to_string())
+    )
+}
+
+pub fn execute_revoke_claim_operator(
+    storage: &mut dyn Storage,
+    address: Addr,
+    operator: Addr,
+) -> Result<Response, ContractError> {
+    CLAIM_OPERATOR_MAP.remove(storage, (address.clone(), operator.clone()));
+
+    Ok(Response::new()
+        .add_attribute("action", "revoke_claim_operator")
+        .add_attribute("owner", address)
+        .add_attribute("operator", operator)
+    )
+}
+
+pub fn execute_set_withdraw_address(
+    storage: &mut dyn Storage,
+    address: Addr,
+    withdraw_address: Option<Addr>,
+) -> Result<Response, ContractError> {
+    match withdraw_address.as_ref() {
+        Some(withdraw_address) => WITHDRAW_ADDRESS_MAP.save(storage, address.clone(), withdraw_address)?,
+        None => WITHDRAW_ADDRESS_MAP.remove(storage, address.clone()),
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_withdraw_address")
+        .add_attribute("owner", address)
+        .add_attribute("withdraw_address", withdraw_address.map_or(String::new(), |address| address.to_string()))
+    )
+}
+
+/// Resolves whose rewards a claim takes and where they are paid. Users may
+/// name any recipient; approved operators always pay the user's withdraw
+/// address, or the user.
+pub fn claim_parties(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    sender: Addr,
+    owner: Option<Addr>,
+    recipient: Option<Addr>,
+) -> Result<(Addr, Addr), ContractError> {
+    let owner = owner.unwrap_or_else(|| sender.clone());
+    if owner != sender {
+        let expires = match CLAIM_OPERATOR_MAP.may_load(storage, (owner.clone(), sender))? {
+            Some(expires) => expires,
+            None => return Err(ContractError::Unauthorized {  }),
+        };
+        if expires.is_expired(block) {
+            return Err(ContractError::ClaimOperatorExpired {  });
+        }
+        if recipient.is_some() {
+            return Err(ContractError::Unauthorized {  });
+        }
+    }
+
+    let recipient = match recipient {
+        Some(recipient) => recipient,
+        None => WITHDRAW_ADDRESS_MAP.may_load(storage, owner.clone())?.unwrap_or_else(|| owner.clone()),
+    };
+    Ok((owner, recipient))
+}
+
+pub fn execute_propose_owner(
     storage: &mut dyn Storage,
+    block: BlockInfo,
     address: Addr,
     owner: Addr,
+    expires: Option<Expiration>,
 ) -> Result<Response, ContractError> {
     check_owner(storage, address)?;
-    
+
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&block) {
+        return Err(ContractError::OwnerTransferExpired {  });
+    }
+
+    PENDING_OWNER.save(storage, &PendingOwner {
+        new_owner: owner.clone(),
+        expires,
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose_owner")
+        .add_attribute("owner", owner)
+        .add_attribute("expires", expires.to_string())
+    )
+}
+
+pub fn execute_accept_owner(
+    storage: &mut dyn Storage,
+    block: BlockInfo,
+    address: Addr,
+) -> Result<Response, ContractError> {
+    let pending = match PENDING_OWNER.may_load(storage)? {
+        Some(pending) => pending,
+        None => return Err(ContractError::NoPendingOwner {  }),
+    };
+    if pending.new_owner != address {
+        return Err(ContractError::Unauthorized {  });
+    }
+    if pending.expires.is_expired(&block) {
+        return Err(ContractError::OwnerTransferExpired {  });
+    }
+
+    // The deployer's implicit rights go with the transfer.
     CONFIG.update(storage, |mut exists| -> StdResult<_> {
-        exists.owner = owner.clone();
+        exists.owner = pending.new_owner.clone();
+        exists.creator = pending.new_owner.clone();
         Ok(exists)
     })?;
+    PENDING_OWNER.remove(storage);
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_owner")
+        .add_attribute("owner", pending.new_owner)
+    )
+}
+
+/// Either the current owner or the proposed one can drop a pending transfer.
+pub fn execute_cancel_owner(
+    storage: &mut dyn Storage,
+    address: Addr,
+) -> Result<Response, ContractError> {
+    let pending = match PENDING_OWNER.may_load(storage)? {
+        Some(pending) => pending,
+        None => return Err(ContractError::NoPendingOwner {  }),
+    };
+    if pending.new_owner != address {
+        check_owner(storage, address)?;
+    }
+
+    PENDING_OWNER.remove(storage);
 
     Ok(Response::new()
-        .add_attribute("action", "update_owner")
-        .add_attribute("owner", owner.clone())
+        .add_attribute("action", "cancel_owner")
+        .add_attribute("owner", pending.new_owner)
     )
 }
 
@@ -92,7 +477,7 @@ pub fn execute_update_fee_address(
 
     Ok(Response::new()
         .add_attribute("action", "update_fee_address")
-        .add_attribute("fee_address", fee_address.clone())
+        .add_attribute("fee_address", fee_address)
     )
 }
 
@@ -104,16 +489,17 @@ pub fn execute_update_tx_fee(
     check_owner(storage, address)?;
     
     CONFIG.update(storage, |mut exists| -> StdResult<_> {
-        exists.tx_fee = tx_fee.clone();
+        exists.tx_fee = tx_fee;
         Ok(exists)
     })?;
 
     Ok(Response::new()
         .add_attribute("action", "update_tx_fee")
-        .add_attribute("tx_fee", tx_fee.clone())
+        .add_attribute("tx_fee", tx_fee)
     )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_registe_collection(
     storage: &mut dyn Storage,
     address: Addr,
@@ -122,15 +508,20 @@ pub fn execute_registe_collection(
     cw20_address: Addr,
     new_unstaking_fee: Uint128,
     new_unstaking_fee_percent: u64,
+    airdrop_mode: Option<AirdropMode>,
 ) -> Result<Response, ContractError> {
-    check_owner(storage, address)?;
+    check_role(storage, address, Role::Operator)?;
+    let cfg = CONFIG.load(storage)?;
+
+    if new_unstaking_fee_percent > 100 {
+        return Err(ContractError::InvalidFeePercent {  });
+    }
 
     let collection = Collection {
         address: collection_address.clone(),
         owner: owner.clone(),
-        total_airdrop: Uint128::zero(),
-        airdropable: Uint128::zero(),
         airdrop_infos: vec![],
+        native_denoms: vec![cfg.native_token],
         started_at: 0u64,
         duration: 0,
         fee_address: owner.clone(),
@@ -140,7 +531,15 @@ pub fn execute_registe_collection(
         is_enabled: true,
         unstaking_fee: new_unstaking_fee,
         unstaking_fee_percent: new_unstaking_fee_percent,
-        users: vec![]
+        cw20_denoms: vec![],
+        airdrop_mode: airdrop_mode.unwrap_or_default(),
+        vesting: None,
+        lock_tiers: vec![],
+        unbonding_duration: 0,
+        fee_curve: FeeCurve::default(),
+        fee_denom: None,
+        tx_fee: Uint128::zero(),
+        tx_fee_percent: 0,
     };
     COLLECTION_MAP.save(storage, collection_address.clone(), &collection)?;
     Ok(Response::new().add_attribute("action", "registe_collection"))
@@ -153,7 +552,11 @@ pub fn execute_update_collection_fee(
     new_unstaking_fee: Uint128,
     new_unstaking_fee_percent: u64,
 ) -> Result<Response, ContractError> {
-    check_owner(storage, address)?;
+    check_role(storage, address, Role::Operator)?;
+
+    if new_unstaking_fee_percent > 100 {
+        return Err(ContractError::InvalidFeePercent {  });
+    }
 
     let exist = COLLECTION_MAP.load(storage, collection_address.clone());
     match exist {
@@ -165,211 +568,1450 @@ pub fn execute_update_collection_fee(
             Ok(Response::new().add_attribute("action", "update_collection_fee"))
         },
         Err(_) => {
-            return Err(crate::ContractError::InvalidCollection {  });
+            Err(crate::ContractError::InvalidCollection {  })
         }
     }
 }
 
-pub fn execute_update_collection_state(
+pub fn execute_update_fee_curve(
     storage: &mut dyn Storage,
     address: Addr,
     collection_address: Addr,
-    is_show: bool
+    fee_curve: FeeCurve,
 ) -> Result<Response, ContractError> {
-    check_owner(storage, address)?;
+    check_role(storage, address, Role::Operator)?;
 
-    let exist = COLLECTION_MAP.load(storage, collection_address.clone());
-    match exist {
-        Ok(mut collection) => {
-            collection.is_show = is_show;
-            COLLECTION_MAP.save(storage, collection_address, &collection)?;
-
-            Ok(Response::new().add_attribute("action", "update_collection_state"))
-        },
-        Err(_) => {
-            return Err(crate::ContractError::InvalidCollection {  });
+    if let FeeCurve::Stepwise { brackets } = &fee_curve {
+        if brackets.is_empty()
+            || brackets.iter().any(|bracket| bracket.percent > 100)
+            || brackets.windows(2).any(|pair| pair[0].min_remaining >= pair[1].min_remaining) {
+            return Err(ContractError::InvalidFeeCurve {  });
         }
     }
+
+    let mut collection = match COLLECTION_MAP.may_load(storage, collection_address.clone())? {
+        Some(collection) => collection,
+        None => return Err(ContractError::InvalidCollection {  }),
+    };
+    collection.fee_curve = fee_curve;
+    COLLECTION_MAP.save(storage, collection_address.clone(), &collection)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_fee_curve")
+        .add_attribute("collection_address", collection_address)
+    )
 }
 
-pub fn execute_update_collection(
+pub fn execute_update_fee_denom(
     storage: &mut dyn Storage,
     address: Addr,
     collection_address: Addr,
-    new_cw20_address: Addr,
-    new_owner: Addr,
-    new_duration: u64,
-    new_fee_address: Addr,
-    is_enabled: bool,
+    fee_denom: Option<Denom>,
+    tx_fee: Uint128,
 ) -> Result<Response, ContractError> {
-    check_collection_owner(storage, collection_address.clone(), address)?;
+    check_role(storage, address, Role::Operator)?;
 
-    let exist = COLLECTION_MAP.load(storage, collection_address.clone());
-    match exist {
-        Ok(mut collection) => {
-            collection.owner = new_owner.clone();
-            collection.cw20_address = new_cw20_address.clone();
-            collection.duration = new_duration;
-            collection.fee_address = new_fee_address.clone();
-            collection.is_enabled = is_enabled;
-            COLLECTION_MAP.save(storage, collection_address.clone(), &collection)?;
+    let mut collection = match COLLECTION_MAP.may_load(storage, collection_address.clone())? {
+        Some(collection) => collection,
+        None => return Err(ContractError::InvalidCollection {  }),
+    };
+    // Amounts only make sense in their own denom's decimals, so the two move together.
+    collection.tx_fee = if fee_denom.is_some() { tx_fee } else { Uint128::zero() };
+    collection.fee_denom = fee_denom;
+    COLLECTION_MAP.save(storage, collection_address.clone(), &collection)?;
 
-            Ok(Response::new().add_attribute("action", "update_collection"))
-        },
-        Err(_) => {
-            return Err(crate::ContractError::InvalidCollection {  });
-        }
+    Ok(Response::new()
+        .add_attribute("action", "update_fee_denom")
+        .add_attribute("collection_address", collection_address)
+    )
+}
+
+pub fn execute_update_tx_fee_split(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Addr,
+    tx_fee_percent: u64,
+) -> Result<Response, ContractError> {
+    check_role(storage, address, Role::Operator)?;
+
+    if tx_fee_percent > 100 {
+        return Err(ContractError::InvalidFeePercent {  });
     }
+
+    let mut collection = match COLLECTION_MAP.may_load(storage, collection_address.clone())? {
+        Some(collection) => collection,
+        None => return Err(ContractError::InvalidCollection {  }),
+    };
+    collection.tx_fee_percent = tx_fee_percent;
+    COLLECTION_MAP.save(storage, collection_address.clone(), &collection)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_tx_fee_split")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("tx_fee_percent", tx_fee_percent.to_string())
+    )
 }
 
-pub fn transfer_token_message(
+/// Sends `protocol_percent` of a collected fee to `Config.fee_address` and the
+/// rest to the collection's `fee_address`, adding both to the revenue totals.
+pub fn route_fee(
+    storage: &mut dyn Storage,
+    cfg: &Config,
+    collection: &Collection,
     denom: Denom,
     amount: Uint128,
-    receiver: Addr
-) -> Result<CosmosMsg, ContractError> {
+    protocol_percent: u64,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    if amount.is_zero() {
+        return Ok(msgs);
+    }
 
-    match denom.clone() {
-        Denom::Native(native_str) => {
-            return Ok(BankMsg::Send {
-                to_address: receiver.clone().into(),
-                amount: vec![Coin{
-                    denom: native_str.clone(),
-                    amount
-                }]
-            }.into());
+    let protocol_fee = amount.multiply_ratio(protocol_percent, 100u64);
+    let collection_fee = amount - protocol_fee;
+    if !protocol_fee.is_zero() {
+        msgs.push(transfer_token_message(denom.clone(), protocol_fee, cfg.fee_address.clone())?);
+    }
+    if !collection_fee.is_zero() {
+        msgs.push(transfer_token_message(denom.clone(), collection_fee, collection.fee_address.clone())?);
+    }
+
+    let mut revenues = REVENUE_MAP.may_load(storage, collection.address.clone())?.unwrap_or_default();
+    match revenues.iter_mut().find(|revenue| revenue.denom == denom) {
+        Some(revenue) => {
+            revenue.protocol += protocol_fee;
+            revenue.collection += collection_fee;
         },
-        Denom::Cw20(native_token) => {
-            return Ok(CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: native_token.clone().into(),
-                funds: vec![],
-                msg: to_binary(&Cw20ExecuteMsg::Transfer {
-                    recipient: receiver.clone().into(),
-                    amount
-                })?,
-            }));
-        }
+        None => revenues.push(RevenueInfo {
+            denom,
+            protocol: protocol_fee,
+            collection: collection_fee,
+        }),
     }
+    REVENUE_MAP.save(storage, collection.address.clone(), &revenues)?;
+
+    Ok(msgs)
 }
 
-pub fn transfer_from_token_message(
-    denom: Denom,
-    owner: Addr,
-    receiver: Addr,
-    amount: Uint128,
-) -> Result<CosmosMsg, ContractError> {
+pub fn fee_denom(
+    cfg: &Config,
+    collection: &Collection,
+) -> Denom {
+    match &collection.fee_denom {
+        Some(denom) => denom.clone(),
+        None => Denom::Native(cfg.native_token.clone()),
+    }
+}
 
-    match denom.clone() {
-        Denom::Native(_native_str) => {
-            return Err(ContractError::InsufficientCw20 {  });
-        },
-        Denom::Cw20(native_token) => {
-            return Ok(CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: native_token.clone().into(),
-                funds: vec![],
-                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
-                    owner: owner.clone().into(),
-                    recipient: receiver.clone().into(),
-                    amount
-                })?,
-            }));
-        }
+/// `tx_fee` in the collection's fee denom.
+pub fn tx_fee(
+    cfg: &Config,
+    collection: &Collection,
+) -> Uint128 {
+    match &collection.fee_denom {
+        Some(_) => collection.tx_fee,
+        None => cfg.tx_fee,
     }
 }
 
-pub fn get_token_amount(
-    querier: QuerierWrapper,
+/// Native fee attached to a stake, restake or unstake. Collections with a
+/// cw20 fee denom are paid through the `Receive` hook, so nothing counts here.
+pub fn native_fee_paid(
+    storage: &dyn Storage,
+    info: &MessageInfo,
+    collection_address: Addr,
+) -> Result<Uint128, ContractError> {
+    let cfg = CONFIG.load(storage)?;
+    let collection = match COLLECTION_MAP.may_load(storage, collection_address)? {
+        Some(collection) => collection,
+        None => return Err(ContractError::InvalidCollection {  }),
+    };
+    match fee_denom(&cfg, &collection) {
+        Denom::Native(_) if info.funds.is_empty() => Ok(Uint128::zero()),
+        Denom::Native(denom) => Ok(must_pay(info, &denom)?),
+        Denom::Cw20(_) => {
+            nonpayable(info)?;
+            Ok(Uint128::zero())
+        },
+    }
+}
+
+/// Sends back whatever was paid above `required`. Payments are rejected
+/// outright when no fee is due.
+pub fn refund_excess_fee(
+    fee_denom: Denom,
+    fee_paid: Uint128,
+    required: Uint128,
+    recipient: Addr,
+) -> Result<Option<(CosmosMsg, Uint128)>, ContractError> {
+    if required.is_zero() && !fee_paid.is_zero() {
+        return Err(ContractError::Payment(PaymentError::NonPayable {  }));
+    }
+    if fee_paid <= required {
+        return Ok(None);
+    }
+    let refund = fee_paid - required;
+    Ok(Some((transfer_token_message(fee_denom, refund, recipient)?, refund)))
+}
+
+pub fn check_cw20_fee(
+    storage: &dyn Storage,
+    collection_address: Addr,
+    cw20_address: Addr,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(storage)?;
+    let collection = match COLLECTION_MAP.may_load(storage, collection_address)? {
+        Some(collection) => collection,
+        None => return Err(ContractError::InvalidCollection {  }),
+    };
+    if fee_denom(&cfg, &collection) != Denom::Cw20(cw20_address) {
+        return Err(ContractError::InvalidDenom {  });
+    }
+    Ok(Response::new().add_attribute("action", "check_cw20_fee"))
+}
+
+pub fn execute_update_collection_state(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Addr,
+    is_show: bool
+) -> Result<Response, ContractError> {
+    check_role(storage, address, Role::Operator)?;
+
+    let exist = COLLECTION_MAP.load(storage, collection_address.clone());
+    match exist {
+        Ok(mut collection) => {
+            collection.is_show = is_show;
+            COLLECTION_MAP.save(storage, collection_address, &collection)?;
+
+            Ok(Response::new().add_attribute("action", "update_collection_state"))
+        },
+        Err(_) => {
+            Err(crate::ContractError::InvalidCollection {  })
+        }
+    }
+}
+
+pub fn execute_update_collection_denoms(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Addr,
+    native_denoms: Vec<String>,
+) -> Result<Response, ContractError> {
+    check_role(storage, address, Role::Operator)?;
+
+    let exist = COLLECTION_MAP.load(storage, collection_address.clone());
+    match exist {
+        Ok(mut collection) => {
+            collection.native_denoms = native_denoms;
+            COLLECTION_MAP.save(storage, collection_address, &collection)?;
+
+            Ok(Response::new().add_attribute("action", "update_collection_denoms"))
+        },
+        Err(_) => {
+            Err(crate::ContractError::InvalidCollection {  })
+        }
+    }
+}
+
+pub fn execute_update_collection_cw20_denoms(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Addr,
+    cw20_denoms: Vec<Addr>,
+) -> Result<Response, ContractError> {
+    check_role(storage, address, Role::Operator)?;
+
+    let exist = COLLECTION_MAP.load(storage, collection_address.clone());
+    match exist {
+        Ok(mut collection) => {
+            collection.cw20_denoms = cw20_denoms;
+            COLLECTION_MAP.save(storage, collection_address, &collection)?;
+
+            Ok(Response::new().add_attribute("action", "update_collection_cw20_denoms"))
+        },
+        Err(_) => {
+            Err(crate::ContractError::InvalidCollection {  })
+        }
+    }
+}
+
+pub fn execute_propose_collection_owner(
+    storage: &mut dyn Storage,
+    block: BlockInfo,
+    address: Addr,
+    collection_address: Addr,
+    owner: Addr,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    check_collection_owner(storage, collection_address.clone(), address)?;
+
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&block) {
+        return Err(ContractError::OwnerTransferExpired {  });
+    }
+
+    PENDING_COLLECTION_OWNER_MAP.save(storage, collection_address.clone(), &PendingOwner {
+        new_owner: owner.clone(),
+        expires,
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose_collection_owner")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("owner", owner)
+        .add_attribute("expires", expires.to_string())
+    )
+}
+
+pub fn execute_accept_collection_owner(
+    storage: &mut dyn Storage,
+    block: BlockInfo,
+    address: Addr,
+    collection_address: Addr,
+) -> Result<Response, ContractError> {
+    let pending = match PENDING_COLLECTION_OWNER_MAP.may_load(storage, collection_address.clone())? {
+        Some(pending) => pending,
+        None => return Err(ContractError::NoPendingOwner {  }),
+    };
+    if pending.new_owner != address {
+        return Err(ContractError::Unauthorized {  });
+    }
+    if pending.expires.is_expired(&block) {
+        return Err(ContractError::OwnerTransferExpired {  });
+    }
+
+    let mut collection = match COLLECTION_MAP.may_load(storage, collection_address.clone())? {
+        Some(collection) => collection,
+        None => return Err(ContractError::InvalidCollection {  }),
+    };
+    collection.owner = pending.new_owner.clone();
+    COLLECTION_MAP.save(storage, collection_address.clone(), &collection)?;
+    PENDING_COLLECTION_OWNER_MAP.remove(storage, collection_address.clone());
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_collection_owner")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("owner", pending.new_owner)
+    )
+}
+
+/// Either the current collection owner or the proposed one can drop a pending transfer.
+pub fn execute_cancel_collection_owner(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Addr,
+) -> Result<Response, ContractError> {
+    let pending = match PENDING_COLLECTION_OWNER_MAP.may_load(storage, collection_address.clone())? {
+        Some(pending) => pending,
+        None => return Err(ContractError::NoPendingOwner {  }),
+    };
+    if pending.new_owner != address {
+        check_collection_owner(storage, collection_address.clone(), address)?;
+    }
+
+    PENDING_COLLECTION_OWNER_MAP.remove(storage, collection_address.clone());
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_collection_owner")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("owner", pending.new_owner)
+    )
+}
+
+pub fn execute_update_lock_tiers(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Addr,
+    lock_tiers: Vec<LockTier>,
+) -> Result<Response, ContractError> {
+    check_collection_owner(storage, collection_address.clone(), address)?;
+
+    if lock_tiers.iter().any(|tier| tier.multiplier == 0) {
+        return Err(ContractError::InvalidTier {  });
+    }
+
+    let mut collection = COLLECTION_MAP.load(storage, collection_address.clone())?;
+    collection.lock_tiers = lock_tiers;
+    COLLECTION_MAP.save(storage, collection_address.clone(), &collection)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_lock_tiers")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("tiers", collection.lock_tiers.len().to_string())
+    )
+}
+
+pub fn get_lock_tier(
+    collection: &Collection,
+    tier: Option<u32>,
+) -> Result<Option<LockTier>, ContractError> {
+    match tier {
+        Some(tier) => match collection.lock_tiers.get(tier as usize) {
+            Some(lock_tier) => Ok(Some(lock_tier.clone())),
+            None => Err(ContractError::InvalidTier {  }),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Tier `nftinfo` is locked on: its stake-time snapshot, or the current tier
+/// at its index for positions staked before tiers were snapshotted.
+pub fn nft_lock_tier(
+    collection: &Collection,
+    nftinfo: &NftInfo,
+) -> Option<LockTier> {
+    match &nftinfo.lock_tier {
+        Some(lock_tier) => Some(lock_tier.clone()),
+        None => nftinfo.tier.and_then(|tier| collection.lock_tiers.get(tier as usize).cloned()),
+    }
+}
+
+/// Tier duration, or the collection duration for untiered NFTs.
+pub fn lock_duration(
+    collection: &Collection,
+    lock_tier: Option<&LockTier>,
+) -> u64 {
+    match lock_tier {
+        Some(lock_tier) => lock_tier.duration,
+        None => collection.duration,
+    }
+}
+
+pub fn unstaking_fee(
+    collection: &Collection,
+    lock_tier: Option<&LockTier>,
+) -> Uint128 {
+    match lock_tier {
+        Some(lock_tier) => lock_tier.unstaking_fee,
+        None => collection.unstaking_fee,
+    }
+}
+
+/// Fee for unstaking `nftinfo` now, given its effective `lock_time`.
+pub fn early_unstake_fee(
+    collection: &Collection,
+    nftinfo: &NftInfo,
+    lock_time: u64,
+    now: u64,
+) -> Uint128 {
+    if lock_time <= now {
+        return Uint128::zero();
+    }
+    let lock_tier = nft_lock_tier(collection, nftinfo);
+    let fee = unstaking_fee(collection, lock_tier.as_ref());
+    let remaining = lock_time - now;
+
+    match &collection.fee_curve {
+        FeeCurve::Flat => fee,
+        FeeCurve::Linear => {
+            let duration = lock_duration(collection, lock_tier.as_ref());
+            if remaining >= duration {
+                fee
+            } else {
+                fee.multiply_ratio(remaining, duration)
+            }
+        },
+        FeeCurve::Stepwise { brackets } => {
+            match brackets
+                .iter()
+                .filter(|bracket| bracket.min_remaining <= remaining)
+                .max_by_key(|bracket| bracket.min_remaining) {
+                Some(bracket) => fee.multiply_ratio(bracket.percent, 100u64),
+                None => Uint128::zero(),
+            }
+        },
+    }
+}
+
+pub fn execute_update_unbonding_duration(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Addr,
+    unbonding_duration: u64,
+) -> Result<Response, ContractError> {
+    check_collection_owner(storage, collection_address.clone(), address)?;
+
+    let mut collection = COLLECTION_MAP.load(storage, collection_address.clone())?;
+    collection.unbonding_duration = unbonding_duration;
+    COLLECTION_MAP.save(storage, collection_address.clone(), &collection)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_unbonding_duration")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("unbonding_duration", unbonding_duration.to_string())
+    )
+}
+
+pub fn execute_update_collection_vesting(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Addr,
+    vesting: Option<Vesting>,
+) -> Result<Response, ContractError> {
+    check_collection_owner(storage, collection_address.clone(), address)?;
+
+    if let Some(vesting) = vesting.as_ref() {
+        if vesting.duration == 0 || vesting.cliff > vesting.duration {
+            return Err(ContractError::InvalidVesting {  });
+        }
+    }
+
+    let mut collection = COLLECTION_MAP.load(storage, collection_address.clone())?;
+    collection.vesting = vesting;
+    COLLECTION_MAP.save(storage, collection_address.clone(), &collection)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_collection_vesting")
+        .add_attribute("collection_address", collection_address)
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn execute_update_collection(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Addr,
+    new_cw20_address: Addr,
+    new_duration: u64,
+    new_fee_address: Addr,
+    is_enabled: bool,
+    airdrop_mode: Option<AirdropMode>,
+) -> Result<Response, ContractError> {
+    if check_role(storage, address.clone(), Role::Operator).is_err() {
+        check_collection_owner(storage, collection_address.clone(), address)?;
+    }
+
+    let exist = COLLECTION_MAP.load(storage, collection_address.clone());
+    match exist {
+        Ok(mut collection) => {
+            collection.cw20_address = new_cw20_address.clone();
+            collection.duration = new_duration;
+            collection.fee_address = new_fee_address.clone();
+            collection.is_enabled = is_enabled;
+            if let Some(airdrop_mode) = airdrop_mode {
+                collection.airdrop_mode = airdrop_mode;
+            }
+            COLLECTION_MAP.save(storage, collection_address.clone(), &collection)?;
+
+            Ok(Response::new().add_attribute("action", "update_collection"))
+        },
+        Err(_) => {
+            Err(crate::ContractError::InvalidCollection {  })
+        }
+    }
+}
+
+pub fn transfer_token_message(
+    denom: Denom,
+    amount: Uint128,
+    receiver: Addr
+) -> Result<CosmosMsg, ContractError> {
+
+    match denom {
+        Denom::Native(native_str) => {
+            Ok(BankMsg::Send {
+                to_address: receiver.into(),
+                amount: vec![Coin{
+                    denom: native_str,
+                    amount
+                }]
+            }.into())
+        },
+        Denom::Cw20(native_token) => {
+            Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: native_token.into(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: receiver.into(),
+                    amount
+                })?,
+            }))
+        }
+    }
+}
+
+pub fn transfer_from_token_message(
+    denom: Denom,
+    owner: Addr,
+    receiver: Addr,
+    amount: Uint128,
+) -> Result<CosmosMsg, ContractError> {
+
+    match denom {
+        Denom::Native(_native_str) => {
+            Err(ContractError::InsufficientCw20 {  })
+        },
+        Denom::Cw20(native_token) => {
+            Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: native_token.into(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: owner.into(),
+                    recipient: receiver.into(),
+                    amount
+                })?,
+            }))
+        }
+    }
+}
+
+pub fn get_token_amount(
+    querier: QuerierWrapper,
     denom: Denom,
     contract_addr: Addr
 ) -> Result<Uint128, ContractError> {
 
-    match denom.clone() {
+    match denom {
         Denom::Native(native_str) => {
             let native_response: NativeBalanceResponse = querier.query(&QueryRequest::Bank(BankQuery::Balance {
-                address: contract_addr.clone().into(),
+                address: contract_addr.into(),
                 denom: native_str
             }))?;
-            return Ok(native_response.amount.amount);
+            Ok(native_response.amount.amount)
         },
         Denom::Cw20(native_token) => {
             let balance_response: CW20BalanceResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-                contract_addr: native_token.clone().into(),
-                msg: to_binary(&Cw20QueryMsg::Balance {address: contract_addr.clone().into()})?,
+                contract_addr: native_token.into(),
+                msg: to_binary(&Cw20QueryMsg::Balance {address: contract_addr.into()})?,
             }))?;
-            return Ok(balance_response.balance);
+            Ok(balance_response.balance)
+        }
+    }
+}
+
+/// Share of the next airdrop held by one NFT still in lock time.
+pub fn airdrop_weight(
+    collection: &Collection,
+    nftinfo: &NftInfo,
+    now: u64,
+) -> Uint128 {
+    match collection.airdrop_mode {
+        AirdropMode::Even => nftinfo.share(),
+        AirdropMode::TimeWeighted => {
+            let since = nftinfo.staked_at.max(collection.started_at);
+            nftinfo.share() * Uint128::from(now.saturating_sub(since))
         }
     }
 }
 
-pub fn get_in_locktime_nft_count(
+pub fn check_merkle_root(
+    merkle_root: &str,
+) -> Result<Response, ContractError> {
+    let mut root_buf: [u8; 32] = [0; 32];
+    if hex::decode_to_slice(merkle_root, &mut root_buf).is_err() {
+        return Err(ContractError::InvalidMerkleRoot {  })
+    }
+    Ok(Response::new().add_attribute("action", "check_merkle_root"))
+}
+
+/// Merkle leaf for one claim, `sha256(0x00 || "<address>:<amount>")` with the
+/// amount in decimal. The prefix keeps leaves and nodes apart.
+pub fn merkle_leaf(
+    address: &Addr,
+    amount: Uint128,
+) -> [u8; 32] {
+    let leaf = format!("{}:{}", address, amount);
+    Sha256::new()
+        .chain_update([0x00])
+        .chain_update(leaf.as_bytes())
+        .finalize()
+        .into()
+}
+
+/// Merkle node, `sha256(0x01 || min(a, b) || max(a, b))` so proofs don't need
+/// to carry the side of each sibling.
+pub fn merkle_node(
+    a: [u8; 32],
+    b: [u8; 32],
+) -> [u8; 32] {
+    let mut hashes = [a, b];
+    hashes.sort_unstable();
+    Sha256::new()
+        .chain_update([0x01])
+        .chain_update(hashes.concat())
+        .finalize()
+        .into()
+}
+
+/// Proofs are hex encoded sibling hashes from `merkle_leaf(address, amount)`
+/// up to the root, combined with `merkle_node`.
+pub fn verify_merkle_proof(
+    merkle_root: &str,
+    address: &Addr,
+    amount: Uint128,
+    proof: Vec<String>,
+) -> Result<Response, ContractError> {
+    let mut hash = merkle_leaf(address, amount);
+
+    for step in proof {
+        let mut proof_buf: [u8; 32] = [0; 32];
+        if hex::decode_to_slice(step, &mut proof_buf).is_err() {
+            return Err(ContractError::InvalidMerkleProof {  })
+        }
+        hash = merkle_node(hash, proof_buf);
+    }
+
+    let mut root_buf: [u8; 32] = [0; 32];
+    if hex::decode_to_slice(merkle_root, &mut root_buf).is_err() || root_buf != hash {
+        return Err(ContractError::InvalidMerkleProof {  })
+    }
+    Ok(Response::new().add_attribute("action", "verify_merkle_proof"))
+}
+
+/// The position's own setting wins over the owner's collection-wide one.
+pub fn is_auto_relock(
     storage: &dyn Storage,
-    block: BlockInfo,
+    nftinfo: &NftInfo,
+) -> StdResult<bool> {
+    match nftinfo.auto_relock {
+        Some(enabled) => Ok(enabled),
+        None => Ok(AUTO_RELOCK_MAP.may_load(storage, (nftinfo.collection_address.clone(), nftinfo.owner.clone()))?.unwrap_or(false)),
+    }
+}
+
+/// `lock_time` rolled over whole `duration` periods until it is in the future.
+fn roll_lock_time(
+    lock_time: u64,
+    duration: u64,
+    now: u64,
+) -> u64 {
+    if lock_time > now || duration == 0 {
+        return lock_time;
+    }
+    let periods = (now - lock_time) / duration + 1;
+    lock_time + periods * duration
+}
+
+/// `lock_time` rolled over for positions that auto-relock.
+pub fn effective_lock_time(
+    storage: &dyn Storage,
+    collection: &Collection,
+    nftinfo: &NftInfo,
+    now: u64,
+) -> StdResult<u64> {
+    if !is_auto_relock(storage, nftinfo)? {
+        return Ok(nftinfo.lock_time);
+    }
+    let duration = lock_duration(collection, nft_lock_tier(collection, nftinfo).as_ref());
+    Ok(roll_lock_time(nftinfo.lock_time, duration, now))
+}
+
+/// Positions switched on while expired pick up the lock where it ran out
+/// rather than starting a fresh one, which only `Restake` does for a
+/// `tx_fee`. Positions switched off keep the period they are in.
+pub fn execute_set_auto_relock(
+    storage: &mut dyn Storage,
+    now: u64,
+    address: Addr,
     collection_address: Addr,
-) -> Result<Uint128, ContractError> {
-    let mut count = 0;
+    token_ids: Option<Vec<String>>,
+    enabled: bool,
+) -> Result<Response, ContractError> {
     let collection = COLLECTION_MAP.load(storage, collection_address.clone())?;
-    let users = collection.users;
-    for userinfo in users.iter() {
-        count += userinfo.staked_nfts
-            .iter()
-            .filter(|nftinfo| 
-                (nftinfo.lock_time > block.time.seconds() && nftinfo.collection_address == collection_address)
-            ).count();
+
+    let nfts = match token_ids.as_ref() {
+        Some(token_ids) => {
+            let mut nfts = vec![];
+            for token_id in token_ids.iter() {
+                match staked_nft_map().may_load(storage, (collection_address.clone(), token_id.clone()))? {
+                    Some(nftinfo) if nftinfo.owner == address => nfts.push(nftinfo),
+                    _ => return Err(ContractError::NoStakedNft {  }),
+                }
+            }
+            nfts
+        },
+        None => staked_nft_map()
+            .idx
+            .owner
+            .prefix((collection_address.clone(), address.clone()))
+            .range(storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, nftinfo)| nftinfo))
+            .collect::<StdResult<Vec<NftInfo>>>()?,
+    };
+
+    for mut nftinfo in nfts {
+        nftinfo.lock_time = effective_lock_time(storage, &collection, &nftinfo, now)?;
+        if token_ids.is_some() {
+            nftinfo.auto_relock = Some(enabled);
+        }
+        if enabled && nftinfo.auto_relock != Some(false) {
+            let duration = lock_duration(&collection, nft_lock_tier(&collection, &nftinfo).as_ref());
+            nftinfo.lock_time = roll_lock_time(nftinfo.lock_time, duration, now);
+        }
+        staked_nft_map().save(storage, (collection_address.clone(), nftinfo.nft_id.clone()), &nftinfo)?;
     }
 
-    return Ok(Uint128::from(u128::try_from(count).unwrap()));
-        
+    if token_ids.is_none() {
+        if enabled {
+            AUTO_RELOCK_MAP.save(storage, (collection_address.clone(), address.clone()), &true)?;
+        } else {
+            AUTO_RELOCK_MAP.remove(storage, (collection_address.clone(), address.clone()));
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_auto_relock")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("address", address)
+        .add_attribute("enabled", enabled.to_string())
+    )
 }
 
-pub fn get_all_addresses(
+/// NFT count and rarity-weighted shares still in lock time.
+pub fn get_in_locktime_totals(
     storage: &dyn Storage,
+    block: BlockInfo,
     collection_address: Addr,
-) -> Result<Vec<Addr>, ContractError> {
-    let mut addresses = Vec::new();
-    let collection = COLLECTION_MAP.load(storage, collection_address)?;
-    for userinfo in collection.users.iter() {
-        addresses.push(userinfo.address.clone());
+) -> Result<(Uint128, Uint128), ContractError> {
+    let collection = COLLECTION_MAP.load(storage, collection_address.clone())?;
+    let mut count = Uint128::zero();
+    let mut shares = Uint128::zero();
+    for item in staked_nft_map().prefix(collection_address).range(storage, None, None, Order::Ascending) {
+        let (_, nftinfo) = item?;
+        if effective_lock_time(storage, &collection, &nftinfo, block.time.seconds())? > block.time.seconds() {
+            count += Uint128::from(1u128);
+            shares += nftinfo.share();
+        }
     }
-    return Ok(addresses)
+
+    Ok((count, shares))
 }
 
-pub fn get_all_collections(
+/// Token entry first, then the range covering a numeric token id, then the
+/// collection default. Ranges are kept disjoint, so only the one with the
+/// highest start at or below the id can cover it.
+pub fn token_weight(
     storage: &dyn Storage,
-) -> Vec<Addr> {
-    let mut collections = Vec::new();
-    let result: StdResult<Vec<(Addr, Collection)>> = COLLECTION_MAP.range(storage, None, None, Order::Ascending).collect();
-    
-    match result {
-        Ok(all_collections) => {
-            for (address, _collection) in all_collections.iter() {
-                collections.push(address.clone());
+    collection_address: Addr,
+    token_id: String,
+) -> StdResult<u64> {
+    if let Some(weight) = RARITY_WEIGHT_MAP.may_load(storage, (collection_address.clone(), token_id.clone()))? {
+        return Ok(weight);
+    }
+
+    if let Ok(id) = token_id.parse::<u64>() {
+        let range = RARITY_RANGE_MAP
+            .prefix(collection_address.clone())
+            .range(storage, None, Some(Bound::inclusive(id)), Order::Descending)
+            .next()
+            .transpose()?;
+        if let Some((_, range)) = range {
+            if id <= range.end {
+                return Ok(range.weight);
             }
-    
-            return collections;
+        }
+    }
+
+    Ok(RARITY_DEFAULT_MAP.may_load(storage, collection_address)?.unwrap_or(1))
+}
+
+/// A range may replace the one stored at the same start, but must not overlap
+/// any other.
+fn check_range_disjoint(
+    storage: &dyn Storage,
+    collection_address: Addr,
+    range: &RarityRange,
+) -> Result<(), ContractError> {
+    let ranges = RARITY_RANGE_MAP.prefix(collection_address);
+    let before = ranges
+        .range(storage, None, Some(Bound::exclusive(range.start)), Order::Descending)
+        .next()
+        .transpose()?;
+    if let Some((_, before)) = before {
+        if before.end >= range.start {
+            return Err(ContractError::OverlappingRange {  });
+        }
+    }
+
+    let after = ranges
+        .range(storage, Some(Bound::exclusive(range.start)), None, Order::Ascending)
+        .next()
+        .transpose()?;
+    if let Some((start, _)) = after {
+        if start <= range.end {
+            return Err(ContractError::OverlappingRange {  });
+        }
+    }
+    Ok(())
+}
+
+/// Weights only apply to NFTs staked afterwards until `SyncRarityWeights` runs.
+pub fn execute_update_rarity_weights(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Addr,
+    default_weight: Option<u64>,
+    weights: Vec<TokenWeight>,
+    ranges: Vec<RarityRange>,
+) -> Result<Response, ContractError> {
+    check_collection_owner(storage, collection_address.clone(), address)?;
+
+    if let Some(default_weight) = default_weight {
+        if default_weight == 0 {
+            return Err(ContractError::InvalidWeight {  });
+        }
+        RARITY_DEFAULT_MAP.save(storage, collection_address.clone(), &default_weight)?;
+    }
+
+    for token_weight in weights.iter() {
+        if token_weight.weight == 0 {
+            return Err(ContractError::InvalidWeight {  });
+        }
+        RARITY_WEIGHT_MAP.save(storage, (collection_address.clone(), token_weight.token_id.clone()), &token_weight.weight)?;
+    }
+
+    for range in ranges.iter() {
+        if range.weight == 0 || range.start > range.end {
+            return Err(ContractError::InvalidWeight {  });
+        }
+        check_range_disjoint(storage, collection_address.clone(), range)?;
+        RARITY_RANGE_MAP.save(storage, (collection_address.clone(), range.start), range)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "update_rarity_weights")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("weights", weights.len().to_string())
+        .add_attribute("ranges", ranges.len().to_string())
+    )
+}
+
+pub fn execute_remove_rarity_weights(
+    storage: &mut dyn Storage,
+    address: Addr,
+    collection_address: Addr,
+    token_ids: Vec<String>,
+    range_starts: Vec<u64>,
+) -> Result<Response, ContractError> {
+    check_collection_owner(storage, collection_address.clone(), address)?;
+
+    for token_id in token_ids.iter() {
+        RARITY_WEIGHT_MAP.remove(storage, (collection_address.clone(), token_id.clone()));
+    }
+    for start in range_starts.iter() {
+        RARITY_RANGE_MAP.remove(storage, (collection_address.clone(), *start));
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_rarity_weights")
+        .add_attribute("collection_address", collection_address)
+        .add_attribute("weights", token_ids.len().to_string())
+        .add_attribute("ranges", range_starts.len().to_string())
+    )
+}
+
+pub fn get_rarity_weights(
+    storage: &dyn Storage,
+    collection_address: Addr,
+    start_after: Option<String>,
+    limit: usize,
+) -> StdResult<Vec<TokenWeight>> {
+    let start = start_after.map(Bound::exclusive);
+    RARITY_WEIGHT_MAP
+        .prefix(collection_address)
+        .range(storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(token_id, weight)| TokenWeight { token_id, weight }))
+        .collect()
+}
+
+pub fn get_rarity_ranges(
+    storage: &dyn Storage,
+    collection_address: Addr,
+    start_after: Option<u64>,
+    limit: usize,
+) -> StdResult<Vec<RarityRange>> {
+    let start = start_after.map(Bound::exclusive);
+    RARITY_RANGE_MAP
+        .prefix(collection_address)
+        .range(storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, range)| range))
+        .collect()
+}
+
+pub fn get_staked_nfts(
+    storage: &dyn Storage,
+    collection_address: Addr,
+    address: Addr,
+    start_after: Option<String>,
+    limit: usize,
+) -> StdResult<Vec<NftInfo>> {
+    let start = start_after.map(|nft_id| Bound::exclusive((collection_address.clone(), nft_id)));
+    staked_nft_map()
+        .idx
+        .owner
+        .prefix((collection_address, address))
+        .range(storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, nftinfo)| nftinfo))
+        .collect()
+}
+
+pub fn has_staked_nfts(
+    storage: &dyn Storage,
+    collection_address: Addr,
+    address: Addr,
+) -> bool {
+    staked_nft_map()
+        .idx
+        .owner
+        .prefix((collection_address, address))
+        .keys_raw(storage, None, None, Order::Ascending)
+        .next()
+        .is_some()
+}
+
+/// Scans at most `limit` users and keeps those still staking, so a page can
+/// come back short while there is more to read. Also returns where the next
+/// page starts.
+pub fn get_addresses(
+    storage: &dyn Storage,
+    collection_address: Addr,
+    start_after: Option<Addr>,
+    limit: usize,
+) -> StdResult<(Vec<Addr>, Option<Addr>)> {
+    let start = start_after.map(Bound::exclusive);
+    let scanned = user_map()
+        .prefix(collection_address.clone())
+        .keys(storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<Addr>>>()?;
+    let next_start_after = if scanned.len() == limit { scanned.last().cloned() } else { None };
+    let addresses = scanned
+        .into_iter()
+        .filter(|address| has_staked_nfts(storage, collection_address.clone(), address.clone()))
+        .collect();
+    Ok((addresses, next_start_after))
+}
+
+pub fn get_collections(
+    storage: &dyn Storage,
+    start_after: Option<Addr>,
+    limit: usize,
+) -> StdResult<Vec<Addr>> {
+    let start = start_after.map(Bound::exclusive);
+    COLLECTION_MAP
+        .keys(storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect()
+}
+
+pub fn get_role_members(
+    storage: &dyn Storage,
+    role: Role,
+    start_after: Option<Addr>,
+    limit: usize,
+) -> StdResult<Vec<Addr>> {
+    let start = start_after.map(Bound::exclusive);
+    ROLE_MAP
+        .prefix(role.as_str().to_string())
+        .keys(storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect()
+}
+
+pub fn get_collection_managers(
+    storage: &dyn Storage,
+    collection_address: Addr,
+    start_after: Option<Addr>,
+    limit: usize,
+) -> StdResult<Vec<Addr>> {
+    let start = start_after.map(Bound::exclusive);
+    COLLECTION_MANAGER_MAP
+        .prefix(collection_address)
+        .keys(storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect()
+}
+
+pub fn get_claim_operators(
+    storage: &dyn Storage,
+    owner: Addr,
+    start_after: Option<Addr>,
+    limit: usize,
+) -> StdResult<Vec<(Addr, Expiration)>> {
+    let start = start_after.map(Bound::exclusive);
+    CLAIM_OPERATOR_MAP
+        .prefix(owner)
+        .range(storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect()
+}
+
+/// Like `get_addresses`, scans at most `limit` collections and keeps the shown ones.
+pub fn get_shown_collections(
+    storage: &dyn Storage,
+    start_after: Option<Addr>,
+    limit: usize,
+) -> StdResult<(Vec<Addr>, Option<Addr>)> {
+    let start = start_after.map(Bound::exclusive);
+    let scanned = COLLECTION_MAP
+        .range(storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<(Addr, Collection)>>>()?;
+    let next_start_after = if scanned.len() == limit { scanned.last().map(|(address, _)| address.clone()) } else { None };
+    let collections = scanned
+        .into_iter()
+        .filter(|(_, collection)| collection.is_show)
+        .map(|(address, _)| address)
+        .collect();
+    Ok((collections, next_start_after))
+}
+
+pub fn denom_string(
+    denom: &Denom,
+) -> String {
+    match denom {
+        Denom::Native(native_str) => native_str.clone(),
+        Denom::Cw20(cw20_address) => cw20_address.to_string(),
+    }
+}
+
+pub fn add_airdropable(
+    collection: &mut Collection,
+    denom: Denom,
+    amount: Uint128,
+) {
+    let index = collection.airdrop_infos.iter().position(|airdrop_info| airdrop_info.denom == denom);
+    match index {
+        Some(index) => {
+            collection.airdrop_infos[index].airdropable += amount;
+        },
+        None => {
+            collection.airdrop_infos.push(AirdropInfo {
+                denom,
+                total_airdrop: Uint128::zero(),
+                airdropable: amount,
+            });
+        }
+    }
+}
+
+pub fn sub_airdropable(
+    collection: &mut Collection,
+    denom: &Denom,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let index = collection.airdrop_infos.iter().position(|airdrop_info| airdrop_info.denom == *denom);
+    match index {
+        Some(index) if collection.airdrop_infos[index].airdropable >= amount => {
+            collection.airdrop_infos[index].airdropable -= amount;
+            Ok(())
         },
-        Err(_error) => {
-            return  Vec::new();
+        _ => {
+            Err(ContractError::InsufficientCw20 {  })
         }
     }
 }
 
-pub fn get_all_shown_collections(
+pub fn add_fund_info(
+    funds: &mut Vec<FundInfo>,
+    denom: Denom,
+    amount: Uint128,
+) {
+    match funds.iter_mut().find(|fund| fund.denom == denom) {
+        Some(fund) => fund.amount += amount,
+        None => funds.push(FundInfo { denom, amount }),
+    }
+}
+
+fn sub_fund_info(
+    funds: &mut [FundInfo],
+    denom: &Denom,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    match funds.iter_mut().find(|fund| fund.denom == *denom) {
+        Some(fund) if fund.amount >= amount => {
+            fund.amount -= amount;
+            Ok(())
+        },
+        _ => Err(ContractError::InsufficientFunds {  }),
+    }
+}
+
+/// Records funds received for a collection, or as prepaid fee credit when
+/// `collection_address` is `None`.
+pub fn add_funds(
+    storage: &mut dyn Storage,
+    collection_address: Option<Addr>,
+    denom: Denom,
+    amount: Uint128,
+) -> StdResult<()> {
+    if let Some(collection_address) = collection_address {
+        let mut funds = COLLECTION_FUNDS_MAP.may_load(storage, collection_address.clone())?.unwrap_or_default();
+        add_fund_info(&mut funds, denom.clone(), amount);
+        COLLECTION_FUNDS_MAP.save(storage, collection_address, &funds)?;
+    }
+
+    let mut allocated = ALLOCATED_FUNDS.may_load(storage)?.unwrap_or_default();
+    add_fund_info(&mut allocated, denom, amount);
+    ALLOCATED_FUNDS.save(storage, &allocated)
+}
+
+/// Releases funds paid out of a collection. Fails instead of dipping into
+/// another collection's funds.
+pub fn sub_funds(
+    storage: &mut dyn Storage,
+    collection_address: Option<Addr>,
+    denom: &Denom,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    if let Some(collection_address) = collection_address {
+        let mut funds = COLLECTION_FUNDS_MAP.may_load(storage, collection_address.clone())?.unwrap_or_default();
+        sub_fund_info(&mut funds, denom, amount)?;
+        COLLECTION_FUNDS_MAP.save(storage, collection_address, &funds)?;
+    }
+
+    let mut allocated = ALLOCATED_FUNDS.may_load(storage)?.unwrap_or_default();
+    sub_fund_info(&mut allocated, denom, amount)?;
+    ALLOCATED_FUNDS.save(storage, &allocated)?;
+    Ok(())
+}
+
+/// Walks a collection's pools, stream, open merkle airdrops, unclaimed user
+/// rewards and vestings to get what it owes, independently of the ledger.
+/// Streamed rewards count until settled, so flooring dust stays a liability.
+pub fn collection_liabilities(
     storage: &dyn Storage,
-) -> Vec<Addr> {
-    let mut collections = Vec::new();
-    let result: StdResult<Vec<(Addr, Collection)>> = COLLECTION_MAP.range(storage, None, None, Order::Ascending).collect();
-    
-    match result {
-        Ok(all_collections) => {
-            for (address, collection) in all_collections.iter() {
-                if collection.is_show {
-                    collections.push(address.clone());
-                }
-            }
-    
-            return collections;
+    collection: &Collection,
+) -> StdResult<Vec<FundInfo>> {
+    let collection_address = collection.address.clone();
+    let mut funds: Vec<FundInfo> = vec![];
+    for airdrop_info in collection.airdrop_infos.iter() {
+        add_fund_info(&mut funds, airdrop_info.denom.clone(), airdrop_info.airdropable);
+    }
+
+    if let Some(stream) = STREAM_MAP.may_load(storage, collection_address.clone())? {
+        add_fund_info(&mut funds, stream.denom.clone(), stream.remaining + stream.unsettled);
+    }
+    for item in user_map().prefix(collection_address.clone()).range(storage, None, None, Order::Ascending) {
+        let (_, userinfo) = item?;
+        for earn_info in userinfo.earn_infos.iter() {
+            add_fund_info(&mut funds, earn_info.denom.clone(), earn_info.claimable);
+        }
+    }
+
+    for item in VESTING_MAP.prefix(collection_address.clone()).range(storage, None, None, Order::Ascending) {
+        let (_, vestings) = item?;
+        for vesting in vestings {
+            add_fund_info(&mut funds, vesting.denom, vesting.amount - vesting.released);
+        }
+    }
+
+    for item in OPEN_MERKLE_AIRDROP_MAP.prefix(collection_address).keys(storage, None, None, Order::Ascending) {
+        let airdrop = MERKLE_AIRDROP_MAP.load(storage, item?)?;
+        add_fund_info(&mut funds, airdrop.denom, airdrop.total_amount - airdrop.claimed_amount);
+    }
+
+    funds.retain(|fund| !fund.amount.is_zero());
+    Ok(funds)
+}
+
+pub fn allocated_amount(
+    storage: &dyn Storage,
+    denom: &Denom,
+) -> StdResult<Uint128> {
+    let allocated = ALLOCATED_FUNDS.may_load(storage)?.unwrap_or_default();
+    Ok(allocated
+        .iter()
+        .find(|fund| fund.denom == *denom)
+        .map_or(Uint128::zero(), |fund| fund.amount))
+}
+
+pub fn add_user_reward(
+    userinfo: &mut UserInfo,
+    denom: Denom,
+    amount: Uint128,
+) {
+    let index = userinfo.earn_infos.iter().position(|earn_info| earn_info.denom == denom);
+    match index {
+        Some(index) => {
+            userinfo.earn_infos[index].claimable += amount;
         },
-        Err(_error) => {
-            return  Vec::new();
+        None => {
+            userinfo.earn_infos.push(EarnInfo {
+                denom,
+                total_earned: Uint128::zero(),
+                claimable: amount,
+            });
+        }
+    }
+}
+
+/// Moves a claim into vesting when the collection has a schedule. Returns
+/// false when the amount should be paid out right away.
+pub fn vest_reward(
+    storage: &mut dyn Storage,
+    now: u64,
+    collection: &Collection,
+    address: Addr,
+    denom: Denom,
+    amount: Uint128,
+) -> StdResult<bool> {
+    let vesting = match collection.vesting.as_ref() {
+        Some(vesting) => vesting,
+        None => return Ok(false),
+    };
+
+    let key = (collection.address.clone(), address);
+    let mut vestings = VESTING_MAP.may_load(storage, key.clone())?.unwrap_or_default();
+    vestings.push(VestingInfo {
+        denom,
+        amount,
+        released: Uint128::zero(),
+        start: now,
+        cliff: vesting.cliff,
+        duration: vesting.duration,
+    });
+    VESTING_MAP.save(storage, key, &vestings)?;
+    Ok(true)
+}
+
+/// Settles the stream and takes the sender's claimable balance for `denom`,
+/// or for every denom when `None`. Returns (denom, amount, vested) per token;
+/// amounts not moved into vesting are released from the ledger for the
+/// caller to send.
+pub fn take_claimable(
+    storage: &mut dyn Storage,
+    now: u64,
+    collection: &Collection,
+    address: Addr,
+    denom: Option<&Denom>,
+) -> Result<Vec<(Denom, Uint128, bool)>, ContractError> {
+    let mut userinfo = update_user_shares(
+        storage, 
+        now, 
+        collection.address.clone(), 
+        address.clone(), 
+        Uint128::zero(), 
+        Uint128::zero()
+    )?;
+
+    let mut claimed: Vec<(Denom, Uint128)> = vec![];
+    for earn_info in userinfo.earn_infos.iter_mut() {
+        if earn_info.claimable.is_zero() || denom.is_some_and(|denom| *denom != earn_info.denom) {
+            continue;
         }
+        claimed.push((earn_info.denom.clone(), earn_info.claimable));
+        earn_info.total_earned += earn_info.claimable;
+        earn_info.claimable = Uint128::zero();
     }
-}
\ No newline at end of file
+    if claimed.is_empty() {
+        return Ok(vec![]);
+    }
+    user_map().save(storage, (collection.address.clone(), address.clone()), &userinfo)?;
+
+    let mut rewards: Vec<(Denom, Uint128, bool)> = vec![];
+    for (denom, amount) in claimed {
+        let vested = vest_reward(storage, now, collection, address.clone(), denom.clone(), amount)?;
+        if !vested {
+            sub_funds(storage, Some(collection.address.clone()), &denom, amount)?;
+        }
+        rewards.push((denom, amount, vested));
+    }
+    Ok(rewards)
+}
+
+pub fn load_stream(
+    storage: &dyn Storage,
+    collection_address: Addr,
+) -> StdResult<StreamInfo> {
+    let stream = STREAM_MAP.may_load(storage, collection_address)?;
+    Ok(stream.unwrap_or(StreamInfo {
+        denom: Denom::Native(String::new()),
+        reward_per_second: Uint128::zero(),
+        reward_per_share: Decimal256::zero(),
+        total_shares: Uint128::zero(),
+        remaining: Uint128::zero(),
+        total_streamed: Uint128::zero(),
+        last_updated: 0,
+        unsettled: Uint128::zero(),
+    }))
+}
+
+/// Moves the rewards emitted since `last_updated` into `reward_per_share`,
+/// never emitting more than the funded `remaining` amount.
+pub fn accrue_stream(
+    stream: &mut StreamInfo,
+    now: u64,
+) {
+    if now <= stream.last_updated {
+        return;
+    }
+    let elapsed = now - stream.last_updated;
+    stream.last_updated = now;
+
+    if stream.total_shares.is_zero() || stream.reward_per_second.is_zero() {
+        return;
+    }
+
+    let emitted = stream.reward_per_second
+        .checked_mul(stream.total_shares)
+        .and_then(|amount| amount.checked_mul(Uint128::from(elapsed)))
+        .unwrap_or(Uint128::MAX)
+        .min(stream.remaining);
+    if emitted.is_zero() {
+        return;
+    }
+
+    stream.reward_per_share += Decimal256::from_ratio(emitted, stream.total_shares);
+    stream.remaining -= emitted;
+    stream.total_streamed += emitted;
+    stream.unsettled += emitted;
+}
+
+pub fn pending_stream_reward(
+    stream: &StreamInfo,
+    userinfo: &UserInfo,
+) -> StdResult<Uint128> {
+    let pending = Uint256::from(userinfo.shares) * (stream.reward_per_share - userinfo.reward_per_share);
+    Ok(Uint128::try_from(pending)?)
+}
+
+/// Credits the user's pending stream reward and brings them up to the current index.
+/// Must be called before the user's shares change.
+pub fn settle_stream_reward(
+    stream: &mut StreamInfo,
+    userinfo: &mut UserInfo,
+) -> StdResult<()> {
+    let pending = pending_stream_reward(stream, userinfo)?;
+    userinfo.reward_per_share = stream.reward_per_share;
+    stream.unsettled = stream.unsettled.saturating_sub(pending);
+    if !pending.is_zero() {
+        add_user_reward(userinfo, stream.denom.clone(), pending);
+    }
+    Ok(())
+}
+
+/// Accrues the collection stream, settles the user's pending reward and applies
+/// a change to their staked shares. Creates the user's position if missing.
+pub fn update_user_shares(
+    storage: &mut dyn Storage,
+    now: u64,
+    collection_address: Addr,
+    address: Addr,
+    shares_added: Uint128,
+    shares_removed: Uint128,
+) -> StdResult<UserInfo> {
+    let mut stream = load_stream(storage, collection_address.clone())?;
+    accrue_stream(&mut stream, now);
+
+    let mut userinfo = user_map()
+        .may_load(storage, (collection_address.clone(), address.clone()))?
+        .unwrap_or(UserInfo {
+            address: address.clone(),
+            earn_infos: vec![],
+            shares: Uint128::zero(),
+            reward_per_share: stream.reward_per_share,
+        });
+    settle_stream_reward(&mut stream, &mut userinfo)?;
+
+    userinfo.shares = userinfo.shares + shares_added - shares_removed;
+    stream.total_shares = stream.total_shares + shares_added - shares_removed;
+
+    user_map().save(storage, (collection_address.clone(), address), &userinfo)?;
+    STREAM_MAP.save(storage, collection_address, &stream)?;
+    Ok(userinfo)
+}